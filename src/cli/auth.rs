@@ -0,0 +1,60 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct Auth {
+    #[arg(
+        long,
+        value_name = "FILENAME",
+        help = "File with one PRINCIPAL:KEY per line, keys are sent as X-Api-Key"
+    )]
+    pub api_keys: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILENAME",
+        help = "htpasswd file with bcrypt hashes for basic authentication"
+    )]
+    pub htpasswd: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILENAME",
+        help = "JWKS file to validate JWT bearer tokens, the subject is used as principal"
+    )]
+    pub jwks: Option<PathBuf>,
+    #[arg(long, requires = "jwks", value_name = "ISSUER")]
+    pub jwt_issuer: Option<String>,
+    #[arg(long, requires = "jwks", value_name = "AUDIENCE")]
+    pub jwt_audience: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILENAME",
+        help = "TOML file mapping principals to the topics they may produce to"
+    )]
+    pub permissions: Option<PathBuf>,
+}
+
+impl Auth {
+    pub fn enabled(&self) -> bool {
+        self.api_keys.is_some()
+            || self.htpasswd.is_some()
+            || self.jwks.is_some()
+            || self.permissions.is_some()
+    }
+}
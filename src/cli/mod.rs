@@ -15,15 +15,24 @@
  */
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use clap::{ColorChoice, Parser};
 
+pub use auth::Auth;
 pub use producer::Producer;
+pub use rate_limit::{PrincipalRateLimit, RateLimit};
 pub use server::*;
+pub use tls::Tls;
 
+pub mod auth;
+pub mod config;
+pub mod preflight;
 pub mod producer;
+pub mod rate_limit;
 pub mod schema_registry;
 pub mod server;
+pub mod tls;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -35,25 +44,141 @@ pub mod server;
     color = ColorChoice::Auto
 )]
 pub struct Cli {
+    #[arg(
+        long,
+        env = "KAFKA_PROXY_CONFIG",
+        value_name = "FILENAME",
+        help = "Read defaults from a TOML or YAML configuration file"
+    )]
+    pub config: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Print the effective configuration with secrets redacted and exit"
+    )]
+    pub print_config: bool,
     #[arg(
         long = "prometheus",
         env = "KAFKA_PROXY_PROMETHEUS_ADDRESS",
         value_name = "ADDRESS"
     )]
     pub prometheus_address: Option<SocketAddr>,
+    #[arg(
+        long,
+        requires_all = ["prometheus_address", "prometheus_tls_key"],
+        env = "KAFKA_PROXY_PROMETHEUS_TLS_CERT",
+        value_name = "FILENAME",
+        help = "PEM encoded certificate chain to serve metrics via HTTPS"
+    )]
+    pub prometheus_tls_cert: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "prometheus_tls_cert",
+        env = "KAFKA_PROXY_PROMETHEUS_TLS_KEY",
+        value_name = "FILENAME"
+    )]
+    pub prometheus_tls_key: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "prometheus_tls_cert",
+        env = "KAFKA_PROXY_PROMETHEUS_TLS_CLIENT_CA",
+        value_name = "FILENAME",
+        help = "Require client certificates issued by one of these CAs for metrics"
+    )]
+    pub prometheus_tls_client_ca: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "KAFKA_PROXY_READY_MAX_QUEUED_MESSAGES",
+        value_name = "COUNT",
+        help = "Report not ready while more messages are queued in the producer"
+    )]
+    pub ready_max_queued_messages: Option<u64>,
+    #[arg(
+        long,
+        env = "KAFKA_PROXY_DRAIN_TIMEOUT",
+        value_name = "SECONDS",
+        default_value_t = 30,
+        help = "Time to deliver in-flight messages on shutdown before writing them to the dead letters"
+    )]
+    pub drain_timeout: u64,
     #[command(subcommand)]
     pub server: ServerCommand,
     #[command(flatten, next_help_heading = "Kafka Options")]
     pub producer: Producer,
 }
 
+impl Cli {
+    pub fn prometheus_tls(&self) -> Tls {
+        Tls {
+            tls_cert: self.prometheus_tls_cert.clone(),
+            tls_key: self.prometheus_tls_key.clone(),
+            tls_client_ca: self.prometheus_tls_client_ca.clone(),
+            tls_subject_header: None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Cli;
-    use clap::CommandFactory;
+    use crate::cli::{Cli, ServerCommand};
+    use clap::{CommandFactory, Parser};
 
     #[test]
     fn verify_cli() {
         Cli::command().debug_assert();
     }
+
+    #[test]
+    fn parse_rate_limit() {
+        let parse = |rate: &str| {
+            Cli::try_parse_from([
+                "kafka-proxy",
+                "-t",
+                "topic",
+                "udp",
+                "--rate-limit-per-ip",
+                rate,
+                "127.0.0.1:9999",
+            ])
+        };
+        assert!(parse("2.5").is_ok());
+        for rate in ["0", "-1", "NaN", "inf", "fast"] {
+            assert!(parse(rate).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_multi_server() {
+        let cli = Cli::try_parse_from([
+            "kafka-proxy",
+            "--topic",
+            "default",
+            "multi",
+            "--server",
+            "--name api --topic events rest --address 127.0.0.1:8081",
+            "--server",
+            "--name 'raw udp' udp 127.0.0.1:9999",
+        ])
+        .unwrap();
+        let ServerCommand::Multi(multi) = cli.server else {
+            panic!("Expected multi server");
+        };
+        assert_eq!(multi.servers.len(), 2);
+        assert_eq!(multi.servers[0].name.as_deref(), Some("api"));
+        assert_eq!(multi.servers[0].topic.as_deref(), Some("events"));
+        assert_eq!(multi.servers[0].server.name(), "rest");
+        assert_eq!(multi.servers[1].name.as_deref(), Some("raw udp"));
+        assert_eq!(multi.servers[1].server.name(), "udp");
+
+        assert!(
+            Cli::try_parse_from([
+                "kafka-proxy",
+                "--topic",
+                "default",
+                "multi",
+                "--server",
+                "--name 'api rest",
+            ])
+            .is_err()
+        );
+    }
 }
@@ -0,0 +1,68 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use clap::Args;
+
+#[derive(Debug, Clone, Args)]
+pub struct Preflight {
+    #[arg(
+        long,
+        env = "KAFKA_PROXY_PREFLIGHT",
+        help = "Verify that the brokers are reachable and the topics exist before starting the servers"
+    )]
+    pub preflight: bool,
+    #[arg(
+        long,
+        requires = "preflight",
+        env = "KAFKA_PROXY_PREFLIGHT_TIMEOUT",
+        value_name = "SECONDS",
+        default_value_t = 0,
+        help = "Keep retrying the preflight checks for this long before giving up"
+    )]
+    pub preflight_timeout: u64,
+    #[arg(
+        long = "preflight-topic",
+        requires = "preflight",
+        value_name = "TOPIC",
+        help = "Additional topic to verify"
+    )]
+    pub preflight_topics: Vec<String>,
+    #[arg(
+        long,
+        requires = "preflight",
+        env = "KAFKA_PROXY_PARTITIONS",
+        value_name = "COUNT",
+        help = "Expected number of partitions of each topic, also used when creating topics"
+    )]
+    pub partitions: Option<u32>,
+    #[arg(
+        long,
+        requires = "preflight",
+        env = "KAFKA_PROXY_CREATE_TOPICS",
+        help = "Create missing topics"
+    )]
+    pub create_topics: bool,
+    #[arg(
+        long,
+        requires = "create_topics",
+        env = "KAFKA_PROXY_REPLICATION_FACTOR",
+        value_name = "COUNT",
+        default_value_t = -1,
+        allow_negative_numbers = true,
+        help = "Replication factor of created topics (-1 uses the broker default)"
+    )]
+    pub replication_factor: i32,
+}
@@ -17,11 +17,26 @@
 use std::env;
 use std::path::PathBuf;
 
+use crate::cli::preflight::Preflight;
 use crate::cli::schema_registry::SchemaRegistry;
 use anyhow::{Error, Result};
-use clap::Args;
+use clap::{Args, ValueEnum};
 use rdkafka::ClientConfig;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SinkType {
+    Kafka,
+    Stdout,
+    File,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SinkEncoding {
+    Base64,
+    Hex,
+    Json,
+}
+
 #[derive(Debug, Args)]
 pub struct Producer {
     #[arg(
@@ -34,6 +49,14 @@ pub struct Producer {
     pub bootstrap_server: String,
     #[arg(short, long, env = "KAFKA_PROXY_TOPIC")]
     pub topic: String,
+    #[arg(
+        long,
+        env = "KAFKA_PROXY_ALLOWED_TOPIC_PREFIXES",
+        value_name = "PREFIX",
+        value_delimiter = ',',
+        help = "Only allow gRPC clients to produce to the default topic and topics starting with one of these prefixes"
+    )]
+    pub allowed_topic_prefix: Vec<String>,
     #[arg(
         long,
         required = false,
@@ -49,8 +72,40 @@ pub struct Producer {
         value_name = "FILENAME"
     )]
     pub dead_letters: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "KAFKA_PROXY_MAX_IN_FLIGHT",
+        value_name = "COUNT",
+        help = "Reject or pause incoming messages while more messages are in flight"
+    )]
+    pub max_in_flight: Option<u64>,
+    #[arg(
+        long,
+        value_enum,
+        env = "KAFKA_PROXY_SINK",
+        default_value_t = SinkType::Kafka,
+        help = "Write records to stdout or a file instead of producing them to Kafka"
+    )]
+    pub sink: SinkType,
+    #[arg(
+        long,
+        required_if_eq("sink", "file"),
+        env = "KAFKA_PROXY_SINK_FILE",
+        value_name = "FILENAME"
+    )]
+    pub sink_file: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        env = "KAFKA_PROXY_SINK_ENCODING",
+        default_value_t = SinkEncoding::Base64,
+        help = "Encoding of keys, headers and payloads written by the stdout and file sinks"
+    )]
+    pub sink_encoding: SinkEncoding,
     #[command(flatten, next_help_heading = "Schema Registry Options")]
     pub schema_registry: SchemaRegistry,
+    #[command(flatten, next_help_heading = "Preflight Options")]
+    pub preflight: Preflight,
 }
 
 impl Producer {
@@ -61,24 +116,32 @@ impl Producer {
         Ok((key.to_string(), value.to_string()))
     }
 
-    pub fn client_config(&self, defaults: Vec<(&str, &str)>) -> ClientConfig {
-        let mut cfg = ClientConfig::new();
-
-        // defaults
-        for (key, value) in defaults {
-            cfg.set(key, value);
-        }
+    pub fn producer_config(&self) -> Vec<(String, String)> {
+        let mut cfg = Vec::new();
 
         // process environment variables
         for (key, value) in env::vars() {
             if let Some(key) = key.strip_prefix("KAFKA_PROXY_PRODUCER_") {
                 let key = key.to_lowercase().replace('_', ".").replace("..", "_");
-                cfg.set(key, value);
+                cfg.push((key, value));
             }
         }
 
         // process arguments
-        for (key, value) in &self.producer_config {
+        cfg.extend(self.producer_config.iter().cloned());
+
+        cfg
+    }
+
+    pub fn client_config(&self, defaults: Vec<(&str, &str)>) -> ClientConfig {
+        let mut cfg = ClientConfig::new();
+
+        // defaults
+        for (key, value) in defaults {
+            cfg.set(key, value);
+        }
+
+        for (key, value) in self.producer_config() {
             cfg.set(key, value);
         }
 
@@ -0,0 +1,65 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Result, bail};
+use clap::Args;
+
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct RateLimit {
+    #[arg(
+        long,
+        value_name = "MESSAGES_PER_SECOND",
+        value_parser = RateLimit::parse_rate,
+        help = "Limit the rate of messages accepted by this server"
+    )]
+    pub rate_limit: Option<f64>,
+    #[arg(
+        long,
+        value_name = "MESSAGES_PER_SECOND",
+        value_parser = RateLimit::parse_rate,
+        help = "Limit the rate of messages accepted per source IP"
+    )]
+    pub rate_limit_per_ip: Option<f64>,
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Number of messages which may exceed the rate limits at once [default: the rate]"
+    )]
+    pub rate_limit_burst: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct PrincipalRateLimit {
+    #[command(flatten)]
+    pub rate_limit: RateLimit,
+    #[arg(
+        long,
+        value_name = "MESSAGES_PER_SECOND",
+        value_parser = RateLimit::parse_rate,
+        help = "Limit the rate of messages accepted per authenticated principal"
+    )]
+    pub rate_limit_per_principal: Option<f64>,
+}
+
+impl RateLimit {
+    fn parse_rate(s: &str) -> Result<f64> {
+        let rate: f64 = s.parse()?;
+        if !rate.is_finite() || rate <= 0.0 {
+            bail!("Rate must be a positive number");
+        }
+        Ok(rate)
+    }
+}
@@ -16,7 +16,7 @@
 
 use clap::Args;
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, Args)]
 pub struct SchemaRegistry {
     #[arg(long, env = "KAFKA_PROXY_SCHEMA_REGISTRY_URL")]
     pub schema_registry_url: Option<String>,
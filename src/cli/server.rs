@@ -20,9 +20,11 @@ use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
-use clap::{Args, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-#[derive(Debug, Subcommand)]
+use crate::cli::{Auth, PrincipalRateLimit, RateLimit, Tls};
+
+#[derive(Debug, Clone, PartialEq, Subcommand)]
 pub enum ServerCommand {
     #[command(name = "stdin", long_about = "Read one message per line from stdin")]
     StdIn(StdInServer),
@@ -56,9 +58,159 @@ pub enum ServerCommand {
     #[cfg(feature = "nng")]
     #[command(name = "nng", long_about = "Receive messages via NNG")]
     Nng(NngServer),
+    #[cfg(feature = "mqtt")]
+    #[command(name = "mqtt", long_about = "Receive messages published via MQTT")]
+    Mqtt(MqttServer),
+    #[cfg(feature = "grpc")]
+    #[command(name = "grpc", long_about = "Receive messages via gRPC")]
+    Grpc(GrpcServer),
+    #[command(
+        name = "syslog",
+        long_about = "Receive RFC 5424 and RFC 3164 syslog messages as JSON records"
+    )]
+    Syslog(SyslogServer),
+    #[command(
+        name = "statsd",
+        long_about = "Receive StatsD and DogStatsD metrics as JSON records"
+    )]
+    Statsd(StatsdServer),
+    #[command(
+        name = "graphite",
+        long_about = "Receive Graphite plaintext metrics as JSON records"
+    )]
+    Graphite(GraphiteServer),
+    #[command(
+        name = "resp",
+        long_about = "Receive messages via the Redis protocol (PUBLISH, LPUSH, RPUSH and XADD)"
+    )]
+    Resp(RespServer),
+    #[cfg(feature = "kafka")]
+    #[command(
+        name = "kafka",
+        long_about = "Receive messages from Kafka clients via the Kafka protocol (Produce API)"
+    )]
+    Kafka(KafkaServer),
+    #[cfg(feature = "forward")]
+    #[command(
+        name = "forward",
+        long_about = "Receive records from Fluentd and Fluent Bit via the Forward protocol"
+    )]
+    Forward(ForwardServer),
+    #[cfg(feature = "otlp")]
+    #[command(
+        name = "otlp",
+        long_about = "Receive OpenTelemetry logs, traces and metrics via OTLP/HTTP and OTLP/gRPC"
+    )]
+    Otlp(OtlpServer),
+    #[command(
+        name = "multi",
+        long_about = "Run multiple servers sharing one Kafka producer"
+    )]
+    Multi(MultiServer),
+}
+
+impl ServerCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerCommand::StdIn(_) => "stdin",
+            ServerCommand::File(_) => "file",
+            ServerCommand::UnixDatagram(_) => "unix-dgram",
+            ServerCommand::UnixSocket(_) => "unix",
+            ServerCommand::UdpSocket(_) => "udp",
+            ServerCommand::TcpSocket(_) => "tcp",
+            #[cfg(feature = "coap")]
+            ServerCommand::Coap(_) => "coap",
+            ServerCommand::Rest(_) => "rest",
+            #[cfg(feature = "posixmq")]
+            ServerCommand::PosixMQ(_) => "posixmq",
+            #[cfg(feature = "nng")]
+            ServerCommand::Nng(_) => "nng",
+            #[cfg(feature = "mqtt")]
+            ServerCommand::Mqtt(_) => "mqtt",
+            #[cfg(feature = "grpc")]
+            ServerCommand::Grpc(_) => "grpc",
+            ServerCommand::Syslog(_) => "syslog",
+            ServerCommand::Statsd(_) => "statsd",
+            ServerCommand::Graphite(_) => "graphite",
+            ServerCommand::Resp(_) => "resp",
+            #[cfg(feature = "kafka")]
+            ServerCommand::Kafka(_) => "kafka",
+            #[cfg(feature = "forward")]
+            ServerCommand::Forward(_) => "forward",
+            #[cfg(feature = "otlp")]
+            ServerCommand::Otlp(_) => "otlp",
+            ServerCommand::Multi(_) => "multi",
+        }
+    }
+
+    pub fn topics(&self) -> Vec<String> {
+        match self {
+            ServerCommand::Multi(multi) => multi
+                .servers
+                .iter()
+                .flat_map(|spec| spec.topic.iter().cloned().chain(spec.server.topics()))
+                .collect(),
+            #[cfg(feature = "mqtt")]
+            ServerCommand::Mqtt(mqtt) => mqtt
+                .topic_mappings
+                .iter()
+                .map(|mapping| mapping.topic.clone())
+                .collect(),
+            #[cfg(feature = "otlp")]
+            ServerCommand::Otlp(otlp) => vec![
+                otlp.logs_topic.clone(),
+                otlp.traces_topic.clone(),
+                otlp.metrics_topic.clone(),
+            ],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AckMode {
+    Delivered,
+    Enqueued,
+    None,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct MultiServer {
+    #[arg(
+        long = "server",
+        required = true,
+        allow_hyphen_values = true,
+        value_name = "COMMAND",
+        value_parser = ServerSpec::parse,
+        help = "Server command line, e.g. \"--name api --topic events rest --address 0.0.0.0:8080\""
+    )]
+    pub servers: Vec<ServerSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Parser)]
+#[command(name = "server", no_binary_name = true)]
+pub struct ServerSpec {
+    #[arg(long, help = "Name used to label metrics [default: the command name]")]
+    pub name: Option<String>,
+    #[arg(long, help = "Topic to produce to instead of the default topic")]
+    pub topic: Option<String>,
+    #[command(subcommand)]
+    pub server: ServerCommand,
+}
+
+impl ServerSpec {
+    fn parse(s: &str) -> Result<ServerSpec, clap::Error> {
+        let args = shlex::split(s).ok_or_else(|| {
+            clap::Error::raw(
+                clap::error::ErrorKind::InvalidValue,
+                format!("Unbalanced quotes in server command line: {s}\n"),
+            )
+        })?;
+        ServerSpec::try_parse_from(args)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct UnixDatagramServer {
     #[arg(
         long,
@@ -70,7 +222,7 @@ pub struct UnixDatagramServer {
     pub path: PathBuf,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct RestServer {
     #[arg(
         short,
@@ -78,10 +230,23 @@ pub struct RestServer {
         default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 8080)
     )]
     pub address: SocketAddr,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AckMode::Delivered,
+        help = "Respond after messages are delivered, enqueued in the producer or immediately"
+    )]
+    pub ack_mode: AckMode,
+    #[command(flatten)]
+    pub tls: Tls,
+    #[command(flatten)]
+    pub auth: Auth,
+    #[command(flatten)]
+    pub rate_limit: PrincipalRateLimit,
 }
 
 #[cfg(feature = "coap")]
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct CoapServer {
     #[arg(
         short,
@@ -89,9 +254,20 @@ pub struct CoapServer {
         default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 5683)
     )]
     pub address: SocketAddr,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AckMode::Delivered,
+        help = "Respond after messages are delivered, enqueued in the producer or immediately"
+    )]
+    pub ack_mode: AckMode,
+    #[command(flatten)]
+    pub auth: Auth,
+    #[command(flatten)]
+    pub rate_limit: PrincipalRateLimit,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct StdInServer {
     #[arg(
         long,
@@ -103,7 +279,7 @@ pub struct StdInServer {
     pub base64: bool,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct FileServer {
     #[arg(
         long,
@@ -117,7 +293,7 @@ pub struct FileServer {
     pub file: PathBuf,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct UnixSocketServer {
     #[arg(
         long,
@@ -127,11 +303,23 @@ pub struct UnixSocketServer {
     pub concurrency_limit: usize,
     #[arg(short, long, default_value_t = false, help = "Base64-decode input")]
     pub base64: bool,
+    #[arg(
+        long = "ack",
+        help = "Reply \"OK <partition>:<offset>\" or \"ERR <reason>\" to each line once it has been handled"
+    )]
+    pub acknowledge: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AckMode::Delivered,
+        help = "Consider messages processed once they are delivered, enqueued in the producer or immediately"
+    )]
+    pub ack_mode: AckMode,
     #[arg()]
     pub file: PathBuf,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct TcpSocketServer {
     #[arg(
         long,
@@ -141,11 +329,25 @@ pub struct TcpSocketServer {
     pub concurrency_limit: usize,
     #[arg(short, long, default_value_t = false, help = "Base64-decode input")]
     pub base64: bool,
+    #[arg(
+        long = "ack",
+        help = "Reply \"OK <partition>:<offset>\" or \"ERR <reason>\" to each line once it has been handled"
+    )]
+    pub acknowledge: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AckMode::Delivered,
+        help = "Consider messages processed once they are delivered, enqueued in the producer or immediately"
+    )]
+    pub ack_mode: AckMode,
     #[arg()]
     pub address: SocketAddr,
+    #[command(flatten)]
+    pub rate_limit: RateLimit,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct UdpSocketServer {
     #[arg(
         long,
@@ -155,10 +357,302 @@ pub struct UdpSocketServer {
     pub concurrency_limit: usize,
     #[arg()]
     pub address: SocketAddr,
+    #[command(flatten)]
+    pub rate_limit: RateLimit,
+    #[arg(
+        long,
+        help = "Write messages dropped due to rate limits to the dead letters"
+    )]
+    pub rate_limit_dead_letter: bool,
+}
+
+#[cfg(feature = "mqtt")]
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct MqttServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 1883)
+    )]
+    pub address: SocketAddr,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged messages per connection"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 1024 * 1024)]
+    pub max_packet_size: usize,
+    #[arg(
+        long = "topic-mapping",
+        value_name = "FILTER=TOPIC",
+        value_parser = TopicMapping::parse,
+        help = "Produce messages published to topics matching the MQTT filter to a Kafka topic, e.g. \"sensors/+/temperature=temperatures\""
+    )]
+    pub topic_mappings: Vec<TopicMapping>,
+    #[arg(long, help = "Header to put the MQTT topic into")]
+    pub topic_header: Option<String>,
+}
+
+#[cfg(feature = "mqtt")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicMapping {
+    pub filter: String,
+    pub topic: String,
+}
+
+#[cfg(feature = "mqtt")]
+impl TopicMapping {
+    fn parse(s: &str) -> anyhow::Result<TopicMapping> {
+        match s.rsplit_once('=') {
+            Some((filter, topic)) if !filter.is_empty() && !topic.is_empty() => Ok(TopicMapping {
+                filter: filter.to_string(),
+                topic: topic.to_string(),
+            }),
+            _ => bail!("Invalid format"),
+        }
+    }
+
+    pub fn matches(&self, topic: &str) -> bool {
+        let mut levels = topic.split('/');
+        for filter in self.filter.split('/') {
+            match (filter, levels.next()) {
+                ("#", _) => return true,
+                ("+", Some(_)) => (),
+                (filter, Some(level)) if filter == level => (),
+                _ => return false,
+            }
+        }
+        levels.next().is_none()
+    }
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct GrpcServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 50051)
+    )]
+    pub address: SocketAddr,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged messages per stream"
+    )]
+    pub concurrency_limit: usize,
+    #[command(flatten)]
+    pub tls: Tls,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct SyslogServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 514)
+    )]
+    pub address: SocketAddr,
+    #[arg(long, value_enum, default_value_t = Transport::Udp)]
+    pub transport: Transport,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged messages"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024)]
+    pub max_message_size: usize,
+    #[arg(long, help = "Use the hostname of messages as record key")]
+    pub key_by_hostname: bool,
+    #[command(flatten)]
+    pub tls: Tls,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct StatsdServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 8125)
+    )]
+    pub address: SocketAddr,
+    #[arg(long, value_enum, default_value_t = Transport::Udp)]
+    pub transport: Transport,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged messages"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024)]
+    pub max_message_size: usize,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Aggregate metrics and produce one record per metric and interval"
+    )]
+    pub flush_interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct GraphiteServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 2003)
+    )]
+    pub address: SocketAddr,
+    #[arg(long, value_enum, default_value_t = Transport::Tcp)]
+    pub transport: Transport,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged messages"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024)]
+    pub max_message_size: usize,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Aggregate metrics and produce one record per metric and interval"
+    )]
+    pub flush_interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct RespServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 6379)
+    )]
+    pub address: SocketAddr,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged messages per connection"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 16 * 1024 * 1024)]
+    pub max_message_size: usize,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AckMode::Delivered,
+        help = "Respond after messages are delivered, enqueued in the producer or immediately"
+    )]
+    pub ack_mode: AckMode,
+    #[arg(
+        long,
+        help = "Prefix of the Kafka topic in front of the channel, list or stream name"
+    )]
+    pub topic_prefix: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "topic_prefix",
+        help = "Produce to the default topic using the channel, list or stream name as record key"
+    )]
+    pub key_by_name: bool,
+    #[command(flatten)]
+    pub tls: Tls,
+}
+
+#[cfg(feature = "kafka")]
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct KafkaServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 9092)
+    )]
+    pub address: SocketAddr,
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        help = "Address clients connect to, returned in metadata responses [default: --address]"
+    )]
+    pub advertised_address: Option<String>,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged records per request"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 100 * 1024 * 1024)]
+    pub max_message_size: usize,
+    #[command(flatten)]
+    pub tls: Tls,
+}
+
+#[cfg(feature = "forward")]
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct ForwardServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 24224)
+    )]
+    pub address: SocketAddr,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Maximum number of unacknowledged records per connection"
+    )]
+    pub concurrency_limit: usize,
+    #[arg(long, value_name = "BYTES", default_value_t = 16 * 1024 * 1024)]
+    pub max_message_size: usize,
+    #[arg(long, help = "Prefix of the Kafka topic in front of the tag")]
+    pub topic_prefix: Option<String>,
+    #[arg(long, help = "Key to put the event time into the record")]
+    pub time_key: Option<String>,
+    #[command(flatten)]
+    pub tls: Tls,
+}
+
+#[cfg(feature = "otlp")]
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct OtlpServer {
+    #[arg(
+        short,
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 4318)
+    )]
+    pub address: SocketAddr,
+    #[arg(
+        long,
+        default_value_t = SocketAddr::new(V4(Ipv4Addr::new(127, 0, 0, 1)), 4317)
+    )]
+    pub grpc_address: SocketAddr,
+    #[arg(long, default_value = "otlp_logs")]
+    pub logs_topic: String,
+    #[arg(long, default_value = "otlp_traces")]
+    pub traces_topic: String,
+    #[arg(long, default_value = "otlp_metrics")]
+    pub metrics_topic: String,
+    #[arg(
+        long,
+        help = "Produce one JSON record per log record, span or metric instead of the protobuf export request"
+    )]
+    pub split: bool,
+    #[arg(long, value_name = "BYTES", default_value_t = 4 * 1024 * 1024)]
+    pub max_message_size: usize,
+    #[command(flatten)]
+    pub tls: Tls,
 }
 
 #[cfg(feature = "posixmq")]
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct PosixMQServer {
     #[arg(short, long, default_value_t = 10)]
     pub capacity: usize,
@@ -173,7 +667,7 @@ pub struct PosixMQServer {
 }
 
 #[cfg(feature = "nng")]
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct NngServer {
     #[arg(
         long,
@@ -187,6 +681,13 @@ pub struct NngServer {
     pub address: String,
     #[arg(long = "ack")]
     pub acknowledge: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AckMode::Delivered,
+        help = "Consider messages processed once they are delivered, enqueued in the producer or immediately"
+    )]
+    pub ack_mode: AckMode,
 }
 
 #[cfg(feature = "nng")]
@@ -0,0 +1,51 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct Tls {
+    #[arg(
+        long,
+        requires = "tls_key",
+        value_name = "FILENAME",
+        help = "PEM encoded certificate chain to serve HTTPS, reloaded on change"
+    )]
+    pub tls_cert: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "tls_cert",
+        value_name = "FILENAME",
+        help = "PEM encoded private key of the certificate"
+    )]
+    pub tls_key: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "tls_cert",
+        value_name = "FILENAME",
+        help = "Require client certificates issued by one of these PEM encoded CAs"
+    )]
+    pub tls_client_ca: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "tls_client_ca",
+        value_name = "HEADER",
+        help = "Add the client certificate subject as a record header"
+    )]
+    pub tls_subject_header: Option<String>,
+}
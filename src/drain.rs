@@ -0,0 +1,96 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::kafka::ProducerHandle;
+
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(1);
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+async fn stop(servers: &mut FuturesUnordered<JoinHandle<()>>) -> Result<()> {
+    while let Some(result) = servers.next().await {
+        result?;
+    }
+    Ok(())
+}
+
+pub async fn run(
+    producer: ProducerHandle,
+    servers: Vec<(String, JoinHandle<()>)>,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let producer = producer.current();
+    let (undelivered, dead_lettered) = producer.losses();
+
+    let abort_handles: Vec<_> = servers
+        .iter()
+        .map(|(name, server)| (name.clone(), server.abort_handle()))
+        .collect();
+    let mut servers: FuturesUnordered<_> = servers.into_iter().map(|(_, server)| server).collect();
+    let stopped = tokio::time::timeout_at(deadline, stop(&mut servers)).await;
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let flushed = {
+        let producer = producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(remaining)).await?
+    };
+
+    match (stopped, flushed) {
+        (Ok(stopped), Ok(())) => stopped?,
+        (stopped, flushed) => {
+            if let Err(e) = flushed {
+                log::warn!("Failed to flush producer: {e}");
+            }
+            log::warn!("Drain timeout expired, dead-lettering remaining messages");
+            producer.abort();
+            let stopped = match stopped {
+                Ok(stopped) => Ok(stopped),
+                Err(_) => tokio::time::timeout(STOP_TIMEOUT, stop(&mut servers)).await,
+            };
+            match stopped {
+                Ok(stopped) => stopped?,
+                Err(_) => {
+                    for (name, server) in abort_handles.iter().filter(|(_, s)| !s.is_finished()) {
+                        log::warn!("Server {name} did not stop, aborting it");
+                        server.abort();
+                    }
+                }
+            }
+        }
+    }
+    let _ = tokio::time::timeout(SETTLE_TIMEOUT, producer.settled()).await;
+
+    let (total_undelivered, total_dead_lettered) = producer.losses();
+    let undelivered = total_undelivered - undelivered;
+    let dead_lettered = total_dead_lettered - dead_lettered;
+    if undelivered == 0 {
+        log::info!("Drained all messages");
+    } else {
+        log::warn!(
+            "{undelivered} messages were not delivered during shutdown, {dead_lettered} written to dead letters, {} lost",
+            undelivered.saturating_sub(dead_lettered)
+        );
+    }
+    Ok(())
+}
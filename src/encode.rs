@@ -0,0 +1,215 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Result, bail};
+use apache_avro::Schema;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use clap::{ColorChoice, Parser, ValueEnum};
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::cli::schema_registry::SchemaRegistry as SchemaRegistryArgs;
+use crate::kafka::schema_registry::SchemaRegistry;
+
+#[allow(unused)]
+mod cli;
+#[allow(unused)]
+mod kafka {
+    pub mod schema_registry;
+    mod serde;
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Encoding {
+    Hex,
+    Base64,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "Convert JSON documents from stdin to Avro without producing them",
+    long_about = None,
+    propagate_version = true,
+    color = ColorChoice::Auto
+)]
+struct Cli {
+    #[arg(
+        long,
+        value_name = "FILENAME",
+        conflicts_with = "schema_registry_url",
+        required_unless_present = "schema_registry_url",
+        help = "Read the schema from a file (framed with schema id 0)"
+    )]
+    schema_file: Option<PathBuf>,
+    #[arg(short, long, help = "Topic used to derive the subject name")]
+    topic: Option<String>,
+    #[arg(short, long, value_enum, default_value_t = Encoding::Hex)]
+    encoding: Encoding,
+    #[command(flatten, next_help_heading = "Schema Registry Options")]
+    schema_registry: SchemaRegistryArgs,
+}
+
+impl Cli {
+    async fn schema_registry(&self) -> Result<SchemaRegistry> {
+        if let Some(path) = &self.schema_file {
+            let schema = Schema::parse_str(&std::fs::read_to_string(path)?)?;
+            return Ok(SchemaRegistry::from_schema(0, schema));
+        }
+
+        let derive_subject =
+            self.schema_registry.schema_id.is_none() && self.schema_registry.record_name.is_none();
+        let topic = match &self.topic {
+            Some(topic) => topic.clone(),
+            None if derive_subject => bail!("--topic is required to derive the subject name"),
+            None => String::new(),
+        };
+        SchemaRegistry::new(topic, &self.schema_registry).await
+    }
+
+    fn format(&self, bytes: &[u8]) -> String {
+        match self.encoding {
+            Encoding::Hex => hex::encode(bytes),
+            Encoding::Base64 => STANDARD.encode(bytes),
+        }
+    }
+
+    fn encode(&self, schema_registry: &SchemaRegistry, input: &str) -> Vec<Result<String>> {
+        documents(input)
+            .into_iter()
+            .map(|json| -> Result<String> { Ok(self.format(&schema_registry.encode_json(json?)?)) })
+            .collect()
+    }
+}
+
+// Malformed documents are skipped up to the end of the line they start in
+fn documents(mut input: &str) -> Vec<serde_json::Result<serde_json::Value>> {
+    let mut documents = Vec::new();
+    loop {
+        let mut stream = serde_json::Deserializer::from_str(input).into_iter::<serde_json::Value>();
+        match stream.next() {
+            None => break,
+            Some(Ok(json)) => {
+                documents.push(Ok(json));
+                input = &input[stream.byte_offset()..];
+            }
+            Some(Err(e)) => {
+                let eof = e.is_eof();
+                documents.push(Err(e));
+                if eof {
+                    break;
+                }
+                let start = input.len() - input.trim_start().len();
+                let line = input[start..]
+                    .find('\n')
+                    .map_or(input.len(), |end| start + end + 1);
+                input = &input[line..];
+            }
+        }
+    }
+    documents
+}
+
+async fn run(cli: Cli) -> Result<bool> {
+    let schema_registry = cli.schema_registry().await?;
+
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+
+    let mut success = true;
+    for (index, encoded) in cli.encode(&schema_registry, &input).into_iter().enumerate() {
+        match encoded {
+            Ok(encoded) => println!("{encoded}"),
+            Err(e) => {
+                eprintln!("Document {}: {e}", index + 1);
+                success = false;
+            }
+        }
+    }
+    Ok(success)
+}
+
+#[tokio::main]
+async fn main() {
+    match run(Cli::parse()).await {
+        Ok(true) => exit(0),
+        Ok(false) => exit(1),
+        Err(e) => {
+            eprintln!("{e}");
+            exit(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cli;
+    use clap::{CommandFactory, Parser};
+    use std::path::PathBuf;
+
+    /// A temporary file that is removed when dropped, even if the test fails.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> TempPath {
+            let path =
+                std::env::temp_dir().join(format!("kafka-proxy-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            TempPath(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[tokio::test]
+    async fn test_encode() {
+        let schema = TempPath::new("encode-schema.avsc");
+        std::fs::write(
+            &schema.0,
+            r#"{"type": "record", "name": "test", "fields": [{"name": "id", "type": "long"}]}"#,
+        )
+        .unwrap();
+        let cli = Cli::try_parse_from([
+            "kafka-proxy-encode",
+            "--schema-file",
+            schema.0.to_str().unwrap(),
+        ])
+        .unwrap();
+        let schema_registry = cli.schema_registry().await.unwrap();
+
+        let encoded = cli.encode(
+            &schema_registry,
+            "{\"id\": 1}\n{\"id\": \n{\"id\": \"wrong\"}\n{\n  \"id\": 2\n}\n",
+        );
+        assert_eq!(encoded.len(), 4);
+        assert_eq!(encoded[0].as_ref().unwrap(), "000000000002");
+        assert!(encoded[1].is_err());
+        assert!(encoded[2].is_err());
+        assert_eq!(encoded[3].as_ref().unwrap(), "000000000004");
+    }
+}
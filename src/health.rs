@@ -0,0 +1,131 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde_json::{Map, Value, json};
+use std::sync::Arc;
+
+use crate::kafka::ProducerHandle;
+
+pub struct Check {
+    pub up: bool,
+    pub details: Map<String, Value>,
+}
+
+pub struct Health {
+    producer: ProducerHandle,
+    max_queued_messages: Option<u64>,
+}
+
+fn status(up: bool) -> &'static str {
+    if up { "UP" } else { "DOWN" }
+}
+
+impl Check {
+    pub fn new(up: bool, details: Value) -> Check {
+        let details = match details {
+            Value::Object(details) => details,
+            _ => Map::new(),
+        };
+        Check { up, details }
+    }
+
+    fn into_json(self) -> Value {
+        let mut json = self.details;
+        json.insert("status".to_string(), status(self.up).into());
+        Value::Object(json)
+    }
+}
+
+impl Health {
+    pub fn new(producer: ProducerHandle, max_queued_messages: Option<u64>) -> Health {
+        Health {
+            producer,
+            max_queued_messages,
+        }
+    }
+
+    pub async fn live_handler() -> Json<Value> {
+        Json(json!({ "status": status(true) }))
+    }
+
+    pub async fn ready_handler(State(health): State<Arc<Health>>) -> (StatusCode, Json<Value>) {
+        let checks = health
+            .producer
+            .current()
+            .checks(health.max_queued_messages)
+            .await;
+        let up = checks.iter().all(|(_, check)| check.up);
+        let checks: Map<String, Value> = checks
+            .into_iter()
+            .map(|(name, check)| (name.to_string(), check.into_json()))
+            .collect();
+        let code = if up {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (
+            code,
+            Json(json!({ "status": status(up), "checks": checks })),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::Cli;
+    use crate::health::Health;
+    use crate::kafka::{KafkaProducer, ProducerHandle, ProducerMetrics};
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use clap::Parser;
+    use prometheus_client::registry::Registry;
+    use std::sync::Arc;
+
+    async fn health(args: &[&str], max_queued_messages: Option<u64>) -> Arc<Health> {
+        let cli = Cli::try_parse_from(["kafka-proxy", "-t", "topic"].iter().chain(args)).unwrap();
+        let metrics = ProducerMetrics::new(&mut Registry::default()).unwrap();
+        let producer = KafkaProducer::new(cli.producer, &metrics).await.unwrap();
+        Arc::new(Health::new(
+            ProducerHandle::new(producer),
+            max_queued_messages,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        let health = health(&["--sink", "stdout", "stdin"], Some(0)).await;
+        let (code, json) = Health::ready_handler(State(health)).await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(json["status"], "UP");
+        assert_eq!(json["checks"]["backlog"]["status"], "UP");
+        assert!(json["checks"].get("kafka").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_not_ready() {
+        let health = health(&["--bootstrap-server", "127.0.0.1:1", "stdin"], None).await;
+        let (code, json) = Health::ready_handler(State(health)).await;
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["status"], "DOWN");
+        assert_eq!(json["checks"]["kafka"]["status"], "DOWN");
+        assert_eq!(json["checks"]["kafka"]["brokers_up"], 0);
+        assert_eq!(json["checks"]["backlog"]["status"], "UP");
+    }
+}
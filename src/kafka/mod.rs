@@ -14,9 +14,11 @@
  * limitations under the License.
  */
 
+mod preflight;
 mod producer;
 mod schema_registry;
 mod serde;
+mod sink;
 mod telemetry_client_context;
 
-pub use producer::KafkaProducer;
+pub use producer::{KafkaProducer, ProducerHandle, ProducerMetrics};
@@ -0,0 +1,146 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::metadata::Metadata;
+use rdkafka::types::RDKafkaErrorCode;
+
+use crate::cli::Producer;
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Preflight {
+    client: Arc<AdminClient<DefaultClientContext>>,
+    topics: BTreeSet<String>,
+    partitions: Option<u32>,
+    create_topics: bool,
+    replication_factor: i32,
+}
+
+impl Preflight {
+    fn new(cfg: &Producer) -> Result<Preflight> {
+        let client = cfg
+            .client_config(vec![
+                ("client.id", "kafka-proxy"),
+                ("bootstrap.servers", &cfg.bootstrap_server),
+            ])
+            .create()?;
+        let mut topics = BTreeSet::from([cfg.topic.clone()]);
+        topics.extend(cfg.preflight.preflight_topics.iter().cloned());
+        Ok(Preflight {
+            client: Arc::new(client),
+            topics,
+            partitions: cfg.preflight.partitions,
+            create_topics: cfg.preflight.create_topics,
+            replication_factor: cfg.preflight.replication_factor,
+        })
+    }
+
+    async fn metadata(&self) -> Result<Metadata> {
+        let client = self.client.clone();
+        let metadata = tokio::task::spawn_blocking(move || {
+            client.inner().fetch_metadata(None, METADATA_TIMEOUT)
+        })
+        .await?
+        .context("Failed to fetch metadata")?;
+        Ok(metadata)
+    }
+
+    async fn create(&self, topics: &[&str]) -> Result<()> {
+        let partitions = self.partitions.map_or(-1, |partitions| partitions as i32);
+        let new_topics: Vec<NewTopic> = topics
+            .iter()
+            .map(|topic| {
+                NewTopic::new(
+                    topic,
+                    partitions,
+                    TopicReplication::Fixed(self.replication_factor),
+                )
+            })
+            .collect();
+        let opts = AdminOptions::new().operation_timeout(Some(METADATA_TIMEOUT));
+        for result in self.client.create_topics(&new_topics, &opts).await? {
+            match result {
+                Ok(topic) => log::info!("Created topic {topic}"),
+                Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => (),
+                Err((topic, e)) => bail!("Failed to create topic {topic}: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn check(&self) -> Result<()> {
+        let mut metadata = self.metadata().await?;
+        let missing: Vec<&str> = self
+            .topics
+            .iter()
+            .filter(|topic| !exists(&metadata, topic))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            if !self.create_topics {
+                bail!("Topics do not exist: {}", missing.join(", "));
+            }
+            self.create(&missing).await?;
+            metadata = self.metadata().await?;
+        }
+
+        for topic in &self.topics {
+            let Some(found) = metadata.topics().iter().find(|t| t.name() == topic) else {
+                bail!("Topic {topic} does not exist");
+            };
+            let partitions = found.partitions().len() as u32;
+            if let Some(expected) = self.partitions
+                && partitions != expected
+            {
+                bail!("Topic {topic} has {partitions} partitions, expected {expected}");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn exists(metadata: &Metadata, topic: &str) -> bool {
+    metadata
+        .topics()
+        .iter()
+        .any(|t| t.name() == topic && t.error().is_none())
+}
+
+pub async fn run(cfg: &Producer) -> Result<()> {
+    let preflight = Preflight::new(cfg)?;
+    let deadline = Instant::now() + Duration::from_secs(cfg.preflight.preflight_timeout);
+    loop {
+        match preflight.check().await {
+            Ok(()) => {
+                log::info!("Preflight checks passed");
+                return Ok(());
+            }
+            Err(e) if Instant::now() < deadline => {
+                log::warn!("Preflight checks failed, retrying: {e:#}");
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+            Err(e) => bail!("Preflight checks failed: {e:#}"),
+        }
+    }
+}
@@ -14,61 +14,193 @@
  * limitations under the License.
  */
 
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock as SyncRwLock};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Error, Result, anyhow, bail};
+use async_trait::async_trait;
 use base64::Engine;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
 use prometheus_client::encoding::EncodeLabelSet;
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
 use prometheus_client::registry::Registry;
-use rdkafka::producer::{FutureProducer, FutureRecord};
-use rdkafka::util::Timeout;
+use serde_json::json;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
 
 use crate::ENGINE;
-use crate::cli::Producer;
+use crate::cli::producer::SinkType;
+use crate::cli::{AckMode, Producer};
+use crate::health::Check;
+use crate::kafka::preflight;
 use crate::kafka::schema_registry::SchemaRegistry;
-use crate::kafka::telemetry_client_context::TelemetryClientContext;
+use crate::kafka::sink::{Delivery, KafkaSink, Record, RecordSink, WriterSink};
+use crate::kafka::telemetry_client_context::{CurrentContext, TelemetryClientContext};
+use crate::server::{Message, MessageSink, Offset};
 
-const TIMEOUT: Timeout = Timeout::After(Duration::from_millis(3000));
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+const HEALTH_INTERVAL: Duration = Duration::from_secs(10);
+const SETTLE_INTERVAL: Duration = Duration::from_millis(10);
+// Matches the default queue.buffering.max.messages of librdkafka
+const MAX_BACKGROUND_TASKS: usize = 100_000;
+const MAX_SCHEMA_REGISTRIES: usize = 1024;
+const SCHEMA_REGISTRY_RETRY: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
 struct RequestLabel {
+    server: String,
     success: bool,
 }
 
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ServerLabel {
+    server: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RejectedLabel {
+    server: String,
+    reason: String,
+}
+
+#[derive(Clone)]
+pub struct ProducerMetrics {
+    context: CurrentContext,
+    producer_requests_counter: Family<RequestLabel, Counter>,
+    producer_sent_counter: Family<ServerLabel, Counter>,
+    rejected_counter: Family<RejectedLabel, Counter>,
+}
+
+#[derive(Clone)]
+enum CachedSchemaRegistry {
+    Ready(Arc<SchemaRegistry>),
+    Failed(Instant, String),
+}
+
 pub struct KafkaProducer {
     topic: String,
-    producer: FutureProducer<TelemetryClientContext>,
-    schema_registry: Option<SchemaRegistry>,
+    allowed_topic_prefixes: Vec<String>,
+    sink: Box<dyn RecordSink + Send + Sync>,
+    context: TelemetryClientContext,
+    schema_registry: Option<crate::cli::schema_registry::SchemaRegistry>,
+    schema_registries: RwLock<HashMap<String, CachedSchemaRegistry>>,
+    schema_registry_check: Mutex<Option<(Instant, Result<(), String>)>>,
     dead_letters: Option<Mutex<File>>,
-    producer_requests_counter: Family<RequestLabel, Counter>,
-    producer_sent_counter: Counter,
+    metrics: ProducerMetrics,
+    aborted: AtomicBool,
+    max_in_flight: Option<u64>,
+    in_flight: Arc<AtomicU64>,
+    undelivered: AtomicU64,
+    dead_lettered: AtomicU64,
+    background: mpsc::Sender<BoxFuture<'static, ()>>,
 }
 
-impl KafkaProducer {
-    pub async fn new(cfg: Producer, registry: &mut Registry) -> Result<KafkaProducer> {
-        let client_config = cfg.client_config(vec![
-            ("client.id", "kafka-proxy"),
-            ("bootstrap.servers", &cfg.bootstrap_server),
-            (
-                "statistics.interval.ms",
-                &crate::metrics::COLLECT_PERIOD_MS.to_string(),
-            ),
-        ]);
-        let context = TelemetryClientContext::new()?;
+#[derive(Clone)]
+pub struct ProducerHandle {
+    current: Arc<SyncRwLock<Arc<KafkaProducer>>>,
+}
+
+struct InFlight(Arc<AtomicU64>);
+
+pub struct ServerSink {
+    server: String,
+    topic: Option<String>,
+    producer: ProducerHandle,
+}
+
+impl ProducerMetrics {
+    pub fn new(registry: &mut Registry) -> Result<ProducerMetrics> {
+        let context = CurrentContext::default();
         registry
             .sub_registry_with_prefix("kafka_producer")
             .register_collector(Box::new(context.clone()));
-        let producer: FutureProducer<TelemetryClientContext, _> =
-            client_config.create_with_context(context)?;
 
-        let schema_registry = match &cfg.schema_registry.schema_registry_url {
-            None => None,
-            Some(_) => Some(SchemaRegistry::new(cfg.topic.clone(), &cfg.schema_registry).await?),
+        let producer_requests_counter = Family::default();
+        registry.register(
+            "requests",
+            "Number of requests",
+            producer_requests_counter.clone(),
+        );
+        let producer_sent_counter = Family::default();
+        registry.register(
+            "produced",
+            "Number of produced Kafka Records",
+            producer_sent_counter.clone(),
+        );
+
+        let rejected_counter = Family::default();
+        registry.register(
+            "rejected",
+            "Number of rejected requests",
+            rejected_counter.clone(),
+        );
+
+        Ok(ProducerMetrics {
+            context,
+            producer_requests_counter,
+            producer_sent_counter,
+            rejected_counter,
+        })
+    }
+}
+
+impl InFlight {
+    fn new(in_flight: &Arc<AtomicU64>) -> InFlight {
+        in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(in_flight.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl KafkaProducer {
+    pub async fn new(cfg: Producer, metrics: &ProducerMetrics) -> Result<KafkaProducer> {
+        if cfg.preflight.preflight && cfg.sink == SinkType::Kafka {
+            preflight::run(&cfg).await?;
+        }
+
+        let context = TelemetryClientContext::new()?;
+        let sink: Box<dyn RecordSink + Send + Sync> = match cfg.sink {
+            SinkType::Kafka => Box::new(KafkaSink::new(&cfg, context.clone())?),
+            SinkType::Stdout => Box::new(WriterSink::new(tokio::io::stdout(), cfg.sink_encoding)),
+            SinkType::File => {
+                let file = match &cfg.sink_file {
+                    None => bail!("No sink file configured"),
+                    Some(path) => {
+                        OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(path)
+                            .await?
+                    }
+                };
+                Box::new(WriterSink::new(file, cfg.sink_encoding))
+            }
+        };
+
+        let (schema_registry, schema_registries) = match &cfg.schema_registry.schema_registry_url {
+            None => (None, HashMap::new()),
+            Some(_) => {
+                let schema_registry =
+                    SchemaRegistry::new(cfg.topic.clone(), &cfg.schema_registry).await?;
+                (
+                    Some(cfg.schema_registry.clone()),
+                    HashMap::from([(
+                        cfg.topic.clone(),
+                        CachedSchemaRegistry::Ready(Arc::new(schema_registry)),
+                    )]),
+                )
+            }
         };
 
         let dead_letters = match cfg.dead_letters {
@@ -83,48 +215,186 @@ impl KafkaProducer {
             }
         };
 
-        let producer_requests_counter = Family::default();
-        registry.register(
-            "requests",
-            "Number of requests",
-            producer_requests_counter.clone(),
-        );
-        let producer_sent_counter = Counter::default();
-        registry.register(
-            "produced",
-            "Number of produced Kafka Records",
-            producer_sent_counter.clone(),
+        let (background, tasks) = mpsc::channel::<BoxFuture<'static, ()>>(MAX_BACKGROUND_TASKS);
+        tokio::spawn(
+            ReceiverStream::new(tasks).for_each_concurrent(MAX_BACKGROUND_TASKS, |task| task),
         );
 
+        metrics.context.set(context.clone());
         Ok(KafkaProducer {
             topic: cfg.topic,
-            producer,
+            allowed_topic_prefixes: cfg.allowed_topic_prefix,
+            sink,
+            context,
             schema_registry,
+            schema_registries: RwLock::new(schema_registries),
+            schema_registry_check: Mutex::new(None),
             dead_letters,
-            producer_requests_counter,
-            producer_sent_counter,
+            metrics: metrics.clone(),
+            aborted: AtomicBool::new(false),
+            max_in_flight: cfg.max_in_flight,
+            in_flight: Arc::new(AtomicU64::new(0)),
+            undelivered: AtomicU64::new(0),
+            dead_lettered: AtomicU64::new(0),
+            background,
         })
     }
 
-    async fn encode(&self, payload: &[u8]) -> Result<Vec<u8>> {
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn flush(&self, timeout: Duration) -> Result<()> {
+        self.sink.flush(timeout)
+    }
+
+    pub fn rejected(&self, server: &str, reason: &str) {
+        self.metrics
+            .rejected_counter
+            .get_or_create(&RejectedLabel {
+                server: server.to_string(),
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::SeqCst);
+        self.sink.purge();
+    }
+
+    pub fn saturated(&self) -> bool {
+        if self.context.queue_full() {
+            return true;
+        }
+        match self.max_in_flight {
+            None => false,
+            Some(max) => {
+                self.in_flight.load(Ordering::SeqCst) >= max
+                    || self.context.queued_messages() >= max
+            }
+        }
+    }
+
+    pub fn losses(&self) -> (u64, u64) {
+        (
+            self.undelivered.load(Ordering::SeqCst),
+            self.dead_lettered.load(Ordering::SeqCst),
+        )
+    }
+
+    pub async fn checks(&self, max_queued_messages: Option<u64>) -> Vec<(&'static str, Check)> {
+        let mut checks = Vec::new();
+
+        if let Some(check) = self.sink.check() {
+            checks.push(("kafka", check));
+        }
+
+        if let Some(cfg) = &self.schema_registry {
+            let mut last_check = self.schema_registry_check.lock().await;
+            let result = match &*last_check {
+                Some((checked, result)) if checked.elapsed() < HEALTH_INTERVAL => result.clone(),
+                _ => {
+                    let schema_registry = SchemaRegistry::new(self.topic.clone(), cfg);
+                    let result = match tokio::time::timeout(HEALTH_TIMEOUT, schema_registry).await {
+                        Ok(Ok(_)) => Ok(()),
+                        Ok(Err(e)) => Err(e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    *last_check = Some((Instant::now(), result.clone()));
+                    result
+                }
+            };
+            let check = match result {
+                Ok(()) => Check::new(true, json!({})),
+                Err(e) => Check::new(false, json!({ "error": e })),
+            };
+            checks.push(("schema_registry", check));
+        }
+
+        let queued_messages = self.context.queued_messages();
+        let up = max_queued_messages.is_none_or(|max| queued_messages <= max);
+        checks.push((
+            "backlog",
+            Check::new(
+                up,
+                json!({ "queued_messages": queued_messages, "max_queued_messages": max_queued_messages }),
+            ),
+        ));
+
+        checks
+    }
+
+    pub fn allowed(&self, topic: &str) -> bool {
+        topic == self.topic
+            || self.allowed_topic_prefixes.is_empty()
+            || self
+                .allowed_topic_prefixes
+                .iter()
+                .any(|prefix| topic.starts_with(prefix.as_str()))
+    }
+
+    async fn schema_registry(&self, topic: &str) -> Result<Arc<SchemaRegistry>> {
+        match self.schema_registries.read().await.get(topic) {
+            Some(CachedSchemaRegistry::Ready(schema_registry)) => {
+                return Ok(schema_registry.clone());
+            }
+            Some(CachedSchemaRegistry::Failed(failed, e))
+                if failed.elapsed() < SCHEMA_REGISTRY_RETRY =>
+            {
+                bail!("{e}");
+            }
+            _ => (),
+        }
+        let cfg = match &self.schema_registry {
+            None => bail!("No Schema Registry configured"),
+            Some(cfg) => cfg,
+        };
+        let cached = match SchemaRegistry::new(topic.to_string(), cfg).await {
+            Ok(schema_registry) => CachedSchemaRegistry::Ready(Arc::new(schema_registry)),
+            Err(e) => CachedSchemaRegistry::Failed(Instant::now(), e.to_string()),
+        };
+        let mut schema_registries = self.schema_registries.write().await;
+        if schema_registries.len() >= MAX_SCHEMA_REGISTRIES
+            && !schema_registries.contains_key(topic)
+        {
+            let evicted = schema_registries
+                .keys()
+                .find(|cached| **cached != self.topic)
+                .cloned();
+            if let Some(evicted) = evicted {
+                schema_registries.remove(&evicted);
+            }
+        }
+        schema_registries.insert(topic.to_string(), cached.clone());
+        match cached {
+            CachedSchemaRegistry::Ready(schema_registry) => Ok(schema_registry),
+            CachedSchemaRegistry::Failed(_, e) => Err(anyhow!(e)),
+        }
+    }
+
+    async fn encode(&self, topic: &str, payload: &[u8]) -> Result<Vec<u8>> {
         match &self.schema_registry {
             None => Ok(Vec::from(payload)),
-            Some(schema_registry) => schema_registry.encode(payload).await,
+            Some(_) => self.schema_registry(topic).await?.encode(payload).await,
         }
     }
 
-    async fn produce(&self, payload: &[u8]) -> Result<()> {
-        let payload = self.encode(payload).await?;
-        let record: FutureRecord<Vec<u8>, Vec<u8>> =
-            FutureRecord::to(&self.topic).payload(&payload);
-        self.producer
-            .send(record, TIMEOUT)
-            .await
-            .map_err(|(e, _)| e)?;
-        Ok(())
+    async fn produce(&self, topic: &str, message: &Message<'_>) -> Result<Delivery> {
+        if self.aborted.load(Ordering::SeqCst) {
+            bail!("Producer is shutting down");
+        }
+        let payload = self.encode(topic, message.payload).await?;
+        let record = Record {
+            topic,
+            key: message.key,
+            headers: message.headers.clone(),
+            payload: &payload,
+        };
+        self.sink.enqueue(record).await
     }
 
-    async fn dead_letter(&self, payload: &[u8]) -> Result<()> {
+    pub async fn dead_letter(&self, payload: &[u8]) -> Result<()> {
         if let Some(file) = &self.dead_letters {
             let mut str = ENGINE.encode(payload);
             str.push('\n');
@@ -134,22 +404,288 @@ impl KafkaProducer {
         Ok(())
     }
 
-    pub async fn send(&self, payload: &[u8]) -> Result<()> {
-        match self.produce(payload).await {
-            Ok(()) => {
-                self.producer_requests_counter
-                    .get_or_create(&RequestLabel { success: true })
-                    .inc();
-                self.producer_sent_counter.inc();
-                Ok(())
+    pub async fn settled(&self) {
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(SETTLE_INTERVAL).await;
+        }
+    }
+
+    async fn background(&self, task: impl Future<Output = ()> + Send + 'static) {
+        if self.background.send(task.boxed()).await.is_err() {
+            log::warn!("Background deliveries are no longer processed");
+        }
+    }
+
+    async fn deliver(
+        &self,
+        server: &str,
+        topic: &str,
+        message: Message<'_>,
+    ) -> Result<Option<Offset>> {
+        match self.produce(topic, &message).await {
+            Ok(delivery) => self.delivered(server, message.payload, delivery).await,
+            Err(e) => self.failed(server, message.payload, e).await,
+        }
+    }
+
+    pub async fn send(
+        self: &Arc<Self>,
+        server: &str,
+        topic: Option<&str>,
+        message: Message<'_>,
+        ack_mode: AckMode,
+    ) -> Result<Option<Offset>> {
+        let topic = message.topic.or(topic).unwrap_or(&self.topic);
+        let in_flight = InFlight::new(&self.in_flight);
+        match ack_mode {
+            AckMode::Delivered => self.deliver(server, topic, message).await,
+            AckMode::Enqueued => {
+                let delivery = match self.produce(topic, &message).await {
+                    Ok(delivery) => delivery,
+                    Err(e) => return self.failed(server, message.payload, e).await,
+                };
+                let producer = self.clone();
+                let server = server.to_string();
+                let payload = message.payload.to_vec();
+                self.background(async move {
+                    let _in_flight = in_flight;
+                    if let Err(e) = producer.delivered(&server, &payload, delivery).await {
+                        log::warn!("{e}");
+                    }
+                })
+                .await;
+                Ok(None)
+            }
+            AckMode::None => {
+                let producer = self.clone();
+                let server = server.to_string();
+                let topic = topic.to_string();
+                let key = message.key.map(<[u8]>::to_vec);
+                let headers: Vec<(String, Vec<u8>)> = message
+                    .headers
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_vec()))
+                    .collect();
+                let payload = message.payload.to_vec();
+                self.background(async move {
+                    let _in_flight = in_flight;
+                    let message = Message {
+                        topic: None,
+                        key: key.as_deref(),
+                        headers: headers
+                            .iter()
+                            .map(|(key, value)| (key.as_str(), value.as_slice()))
+                            .collect(),
+                        payload: &payload,
+                    };
+                    if let Err(e) = producer.deliver(&server, &topic, message).await {
+                        log::warn!("{e}");
+                    }
+                })
+                .await;
+                Ok(None)
             }
-            Err(e) => {
-                self.producer_requests_counter
-                    .get_or_create(&RequestLabel { success: false })
+        }
+    }
+
+    async fn delivered(
+        &self,
+        server: &str,
+        payload: &[u8],
+        delivery: Delivery,
+    ) -> Result<Option<Offset>> {
+        match delivery.await {
+            Ok(offset) => {
+                self.metrics
+                    .producer_requests_counter
+                    .get_or_create(&RequestLabel {
+                        server: server.to_string(),
+                        success: true,
+                    })
                     .inc();
-                self.dead_letter(payload).await?;
-                Err(e)
+                self.metrics
+                    .producer_sent_counter
+                    .get_or_create(&ServerLabel {
+                        server: server.to_string(),
+                    })
+                    .inc();
+                Ok(offset)
             }
+            Err(e) => self.failed(server, payload, e).await,
+        }
+    }
+
+    async fn failed(&self, server: &str, payload: &[u8], e: Error) -> Result<Option<Offset>> {
+        self.metrics
+            .producer_requests_counter
+            .get_or_create(&RequestLabel {
+                server: server.to_string(),
+                success: false,
+            })
+            .inc();
+        self.undelivered.fetch_add(1, Ordering::SeqCst);
+        if self.dead_letters.is_some() {
+            // Servers dead letter rate limited messages too, those aren't undelivered
+            self.dead_letter(payload).await?;
+            self.dead_lettered.fetch_add(1, Ordering::SeqCst);
+        }
+        Err(e)
+    }
+}
+
+impl ProducerHandle {
+    pub fn new(producer: KafkaProducer) -> ProducerHandle {
+        ProducerHandle {
+            current: Arc::new(SyncRwLock::new(Arc::new(producer))),
         }
     }
+
+    pub fn current(&self) -> Arc<KafkaProducer> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(e) => e.into_inner().clone(),
+        }
+    }
+
+    pub fn replace(&self, producer: KafkaProducer) -> Arc<KafkaProducer> {
+        let mut current = match self.current.write() {
+            Ok(current) => current,
+            Err(e) => e.into_inner(),
+        };
+        std::mem::replace(&mut *current, Arc::new(producer))
+    }
+
+    pub fn sink(&self, server: String, topic: Option<String>) -> ServerSink {
+        ServerSink {
+            server,
+            topic,
+            producer: self.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl MessageSink for ServerSink {
+    fn topic(&self) -> String {
+        match &self.topic {
+            Some(topic) => topic.clone(),
+            None => self.producer.current().topic().to_string(),
+        }
+    }
+
+    fn allowed(&self, topic: &str) -> bool {
+        self.topic.as_deref() == Some(topic) || self.producer.current().allowed(topic)
+    }
+
+    fn rejected(&self, reason: &'static str) {
+        self.producer.current().rejected(&self.server, reason);
+    }
+
+    fn saturated(&self) -> bool {
+        self.producer.current().saturated()
+    }
+
+    async fn dead_letter(&self, payload: &[u8]) -> Result<()> {
+        self.producer.current().dead_letter(payload).await
+    }
+
+    async fn send_message(&self, message: Message<'_>) -> Result<()> {
+        self.send_with_ack(message, AckMode::Delivered).await?;
+        Ok(())
+    }
+
+    async fn send_with_ack(
+        &self,
+        message: Message<'_>,
+        ack_mode: AckMode,
+    ) -> Result<Option<Offset>> {
+        self.producer
+            .current()
+            .send(&self.server, self.topic.as_deref(), message, ack_mode)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{AckMode, Cli};
+    use crate::kafka::sink::{Delivery, Record, RecordSink};
+    use crate::kafka::{KafkaProducer, ProducerHandle, ProducerMetrics};
+    use crate::server::{Message, MessageSink};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use clap::Parser;
+    use futures::FutureExt;
+    use prometheus_client::registry::Registry;
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+
+    struct PendingSink;
+
+    #[async_trait]
+    impl RecordSink for PendingSink {
+        async fn enqueue(&self, _record: Record<'_>) -> Result<Delivery> {
+            Ok(futures::future::pending().boxed())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_send() {
+        let cli = Cli::try_parse_from(["kafka-proxy", "-t", "topic", "--sink", "stdout", "stdin"])
+            .unwrap();
+        let metrics = ProducerMetrics::new(&mut Registry::default()).unwrap();
+        let mut producer = KafkaProducer::new(cli.producer, &metrics).await.unwrap();
+        producer.sink = Box::new(PendingSink);
+        let producer = Arc::new(producer);
+
+        let mut send = Box::pin(producer.send(
+            "test",
+            None,
+            Message::from(b"payload".as_slice()),
+            AckMode::Delivered,
+        ));
+        assert!(futures::poll!(&mut send).is_pending());
+        assert_eq!(producer.in_flight.load(Ordering::SeqCst), 1);
+        drop(send);
+        assert_eq!(producer.in_flight.load(Ordering::SeqCst), 0);
+        producer.settled().await;
+    }
+
+    #[tokio::test]
+    async fn test_allowed_topics() {
+        let cli = Cli::try_parse_from([
+            "kafka-proxy",
+            "-t",
+            "topic",
+            "--allowed-topic-prefix",
+            "logs.,metrics.",
+            "--sink",
+            "stdout",
+            "stdin",
+        ])
+        .unwrap();
+        let metrics = ProducerMetrics::new(&mut Registry::default()).unwrap();
+        let producer = KafkaProducer::new(cli.producer, &metrics).await.unwrap();
+
+        assert!(producer.allowed("topic"));
+        assert!(producer.allowed("logs.app"));
+        assert!(producer.allowed("metrics.app"));
+        assert!(!producer.allowed("other"));
+
+        let handle = ProducerHandle::new(producer);
+        let sink = handle.sink("api".to_string(), Some("events".to_string()));
+        assert!(sink.allowed("events"));
+        assert!(sink.allowed("logs.app"));
+        assert!(!sink.allowed("other"));
+        let message = Message {
+            topic: Some("mapped"),
+            ..Message::from(b"payload".as_slice())
+        };
+        assert!(
+            sink.send_with_ack(message, AckMode::Delivered)
+                .await
+                .is_ok()
+        );
+        handle.current().settled().await;
+    }
 }
@@ -86,11 +86,19 @@ impl SchemaRegistry {
         schema_registry: &crate::cli::schema_registry::SchemaRegistry,
     ) -> Result<SchemaRegistry> {
         let (id, schema) = schema(schema_registry, topic_name).await?;
-        Ok(SchemaRegistry { id, schema })
+        Ok(SchemaRegistry::from_schema(id, schema))
+    }
+
+    pub fn from_schema(id: u32, schema: Schema) -> SchemaRegistry {
+        SchemaRegistry { id, schema }
     }
 
     pub async fn encode(&self, payload: &[u8]) -> Result<Vec<u8>> {
         let json = serde_json::from_slice(payload)?;
+        self.encode_json(json)
+    }
+
+    pub fn encode_json(&self, json: serde_json::Value) -> Result<Vec<u8>> {
         let value = deserialize_json(&self.schema, json)?;
         let serialized = apache_avro::to_avro_datum(&self.schema, value)?;
 
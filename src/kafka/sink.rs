@@ -0,0 +1,193 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::Engine;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer as _, PurgeConfig};
+use serde_json::json;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::ENGINE;
+use crate::cli::Producer;
+use crate::cli::producer::SinkEncoding;
+use crate::health::Check;
+use crate::kafka::telemetry_client_context::TelemetryClientContext;
+use crate::server::Offset;
+
+const QUEUE_TIMEOUT: Duration = Duration::from_millis(3000);
+const QUEUE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+pub type Delivery = BoxFuture<'static, Result<Option<Offset>>>;
+
+pub struct Record<'a> {
+    pub topic: &'a str,
+    pub key: Option<&'a [u8]>,
+    pub headers: Vec<(&'a str, &'a [u8])>,
+    pub payload: &'a [u8],
+}
+
+#[async_trait]
+pub trait RecordSink {
+    async fn enqueue(&self, record: Record<'_>) -> Result<Delivery>;
+
+    fn flush(&self, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    fn purge(&self) {}
+
+    fn check(&self) -> Option<Check> {
+        None
+    }
+}
+
+pub struct KafkaSink {
+    producer: FutureProducer<TelemetryClientContext>,
+    context: TelemetryClientContext,
+}
+
+impl KafkaSink {
+    pub fn new(cfg: &Producer, context: TelemetryClientContext) -> Result<KafkaSink> {
+        let client_config = cfg.client_config(vec![
+            ("client.id", "kafka-proxy"),
+            ("bootstrap.servers", &cfg.bootstrap_server),
+            (
+                "statistics.interval.ms",
+                &crate::metrics::COLLECT_PERIOD_MS.to_string(),
+            ),
+        ]);
+        let producer = client_config.create_with_context(context.clone())?;
+        Ok(KafkaSink { producer, context })
+    }
+}
+
+#[async_trait]
+impl RecordSink for KafkaSink {
+    async fn enqueue(&self, record: Record<'_>) -> Result<Delivery> {
+        let mut headers = OwnedHeaders::new_with_capacity(record.headers.len());
+        for (key, value) in record.headers {
+            headers = headers.insert(Header {
+                key,
+                value: Some(value),
+            });
+        }
+        let mut future_record: FutureRecord<[u8], [u8]> = FutureRecord::to(record.topic)
+            .payload(record.payload)
+            .headers(headers);
+        if let Some(key) = record.key {
+            future_record = future_record.key(key);
+        }
+        let deadline = Instant::now() + QUEUE_TIMEOUT;
+        loop {
+            match self.producer.send_result(future_record) {
+                Ok(delivery) => {
+                    return Ok(async move {
+                        let delivery = delivery.await?.map_err(|(e, _)| e)?;
+                        Ok(Some(Offset {
+                            partition: delivery.partition,
+                            offset: delivery.offset,
+                        }))
+                    }
+                    .boxed());
+                }
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), record))
+                    if Instant::now() < deadline =>
+                {
+                    future_record = record;
+                    tokio::time::sleep(QUEUE_RETRY_INTERVAL).await;
+                }
+                Err((e, _)) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn flush(&self, timeout: Duration) -> Result<()> {
+        Ok(self.producer.flush(timeout)?)
+    }
+
+    fn purge(&self) {
+        self.producer
+            .purge(PurgeConfig::default().queue().inflight());
+    }
+
+    fn check(&self) -> Option<Check> {
+        let brokers_up = self.context.brokers_up();
+        Some(Check::new(
+            brokers_up > 0,
+            json!({ "brokers_up": brokers_up }),
+        ))
+    }
+}
+
+pub struct WriterSink<W> {
+    writer: Mutex<W>,
+    encoding: SinkEncoding,
+}
+
+impl<W> WriterSink<W> {
+    pub fn new(writer: W, encoding: SinkEncoding) -> WriterSink<W> {
+        WriterSink {
+            writer: Mutex::new(writer),
+            encoding,
+        }
+    }
+
+    fn encode(&self, bytes: &[u8]) -> serde_json::Value {
+        match self.encoding {
+            SinkEncoding::Base64 => ENGINE.encode(bytes).into(),
+            SinkEncoding::Hex => hex::encode(bytes).into(),
+            SinkEncoding::Json => serde_json::from_slice(bytes)
+                .unwrap_or_else(|_| String::from_utf8_lossy(bytes).into()),
+        }
+    }
+}
+
+#[async_trait]
+impl<W> RecordSink for WriterSink<W>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn enqueue(&self, record: Record<'_>) -> Result<Delivery> {
+        let headers: BTreeMap<&str, serde_json::Value> = record
+            .headers
+            .iter()
+            .map(|(key, value)| (*key, self.encode(value)))
+            .collect();
+        let line = json!({
+            "topic": record.topic,
+            "key": record.key.map(|key| self.encode(key)),
+            "headers": headers,
+            "payload": self.encode(record.payload),
+        });
+        let mut line = serde_json::to_vec(&line)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&line).await?;
+        writer.flush().await?;
+        Ok(futures::future::ok(None).boxed())
+    }
+}
@@ -43,6 +43,60 @@ impl TelemetryClientContext {
         let latest = Arc::new(RwLock::new(Statistics::default()));
         Ok(TelemetryClientContext { latest })
     }
+
+    pub fn brokers_up(&self) -> usize {
+        match self.latest.read() {
+            Ok(stats) => stats
+                .brokers
+                .values()
+                .filter(|broker| broker.state == "UP")
+                .count(),
+            Err(_) => 0,
+        }
+    }
+
+    pub fn queued_messages(&self) -> u64 {
+        match self.latest.read() {
+            Ok(stats) => stats.msg_cnt,
+            Err(_) => 0,
+        }
+    }
+
+    pub fn queue_full(&self) -> bool {
+        match self.latest.read() {
+            Ok(stats) => {
+                (stats.msg_max > 0 && stats.msg_cnt >= stats.msg_max)
+                    || (stats.msg_size_max > 0 && stats.msg_size >= stats.msg_size_max)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+// Replaced producers keep their own statistics while draining, only the current one is exported
+#[derive(Debug, Clone, Default)]
+pub struct CurrentContext(Arc<RwLock<Option<TelemetryClientContext>>>);
+
+impl CurrentContext {
+    pub fn set(&self, context: TelemetryClientContext) {
+        match self.0.write() {
+            Ok(mut current) => *current = Some(context),
+            Err(e) => *e.into_inner() = Some(context),
+        }
+    }
+}
+
+impl Collector for CurrentContext {
+    fn encode(
+        &self,
+        encoder: prometheus_client::encoding::DescriptorEncoder,
+    ) -> std::result::Result<(), std::fmt::Error> {
+        let current = self.0.read().map_err(|_| std::fmt::Error)?;
+        match &*current {
+            Some(context) => context.encode(encoder),
+            None => Ok(()),
+        }
+    }
 }
 
 impl ClientContext for TelemetryClientContext {
@@ -16,22 +16,30 @@
 
 #![forbid(unsafe_code)]
 
-use crate::cli::{Cli, ServerCommand};
-use crate::kafka::KafkaProducer;
+use crate::cli::{ServerCommand, ServerSpec};
+use crate::health::Health;
+use crate::kafka::{KafkaProducer, ProducerHandle, ProducerMetrics};
 use crate::metrics::Metrics;
 use crate::server::Server;
-use anyhow::{Error, Result};
+use crate::tls::TlsAcceptor;
+use anyhow::{Error, Result, bail};
 use base64::alphabet;
 use base64::engine::{GeneralPurpose, GeneralPurposeConfig};
-use clap::Parser;
 use log::SetLoggerError;
 use prometheus_client::registry::Registry;
+use std::collections::HashSet;
 use std::process::exit;
+use std::sync::Arc;
+use std::time::Duration;
 
 mod cli;
+mod drain;
+mod health;
 mod kafka;
 mod metrics;
+mod reload;
 mod server;
+mod tls;
 
 fn configure_logging() -> std::result::Result<(), SetLoggerError> {
     fern::Dispatch::new()
@@ -72,8 +80,14 @@ async fn shutdown_signal() -> Result<()> {
     }
 }
 
-fn server(server: ServerCommand) -> Box<dyn Server + Send> {
-    match server {
+struct ServerInstance {
+    name: String,
+    topic: Option<String>,
+    server: Box<dyn Server + Send>,
+}
+
+fn server(server: ServerCommand) -> Result<Box<dyn Server + Send>> {
+    let server: Box<dyn Server + Send> = match server {
         ServerCommand::Rest(server) => Box::new(server),
         #[cfg(feature = "coap")]
         ServerCommand::Coap(server) => Box::new(server),
@@ -87,33 +101,105 @@ fn server(server: ServerCommand) -> Box<dyn Server + Send> {
         ServerCommand::PosixMQ(server) => Box::new(server),
         #[cfg(feature = "nng")]
         ServerCommand::Nng(server) => Box::new(server),
+        #[cfg(feature = "mqtt")]
+        ServerCommand::Mqtt(server) => Box::new(server),
+        #[cfg(feature = "grpc")]
+        ServerCommand::Grpc(server) => Box::new(server),
+        ServerCommand::Syslog(server) => Box::new(server),
+        ServerCommand::Statsd(server) => Box::new(server),
+        ServerCommand::Graphite(server) => Box::new(server),
+        ServerCommand::Resp(server) => Box::new(server),
+        #[cfg(feature = "kafka")]
+        ServerCommand::Kafka(server) => Box::new(server),
+        #[cfg(feature = "forward")]
+        ServerCommand::Forward(server) => Box::new(server),
+        #[cfg(feature = "otlp")]
+        ServerCommand::Otlp(server) => Box::new(server),
+        ServerCommand::Multi(_) => bail!("Nested multi servers are not supported"),
+    };
+    Ok(server)
+}
+
+fn servers(command: ServerCommand) -> Result<Vec<ServerInstance>> {
+    let specs = match command {
+        ServerCommand::Multi(multi) => multi.servers,
+        server => vec![ServerSpec {
+            name: None,
+            topic: None,
+            server,
+        }],
+    };
+
+    let mut names = HashSet::new();
+    let mut servers = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let name = spec.name.unwrap_or_else(|| spec.server.name().to_string());
+        if !names.insert(name.clone()) {
+            bail!("Duplicate server name: {name}");
+        }
+        servers.push(ServerInstance {
+            name,
+            topic: spec.topic,
+            server: server(spec.server)?,
+        });
     }
+    Ok(servers)
 }
 
 async fn run() -> Result<()> {
     configure_logging()?;
 
-    let (shutdown_trigger_send, shutdown_trigger_recv) = tokio::sync::broadcast::channel(2);
+    let (shutdown_trigger_send, _) = tokio::sync::broadcast::channel(2);
     let (shutdown_send, mut shutdown_recv) = tokio::sync::mpsc::channel(1);
 
-    let cli = Cli::parse();
+    let mut cli = cli::config::parse()?;
+    let topics = cli.server.topics();
+    cli.producer.preflight.preflight_topics.extend(topics);
+    let server_config = cli.server.clone();
+    let prometheus_tls = TlsAcceptor::http(&cli.prometheus_tls())?;
+    let servers = servers(cli.server)?;
     let mut registry = Registry::with_prefix("kafkaproxy");
 
-    let producer = KafkaProducer::new(cli.producer, &mut registry).await?;
-    let server = tokio::spawn(async move {
-        let server = server(cli.server);
-        let result = server.run(producer, shutdown_trigger_recv, shutdown_send);
-        match result.await {
-            Ok(()) => (),
-            Err(e) => {
-                log::error!("{e}")
-            }
-        }
-    });
+    let producer_metrics = ProducerMetrics::new(&mut registry)?;
+    let producer = ProducerHandle::new(KafkaProducer::new(cli.producer, &producer_metrics).await?);
+    let servers: Vec<_> = servers
+        .into_iter()
+        .map(|instance| {
+            let name = instance.name.clone();
+            let sink = Arc::new(producer.sink(instance.name, instance.topic));
+            let shutdown_trigger_recv = shutdown_trigger_send.subscribe();
+            let shutdown_send = shutdown_send.clone();
+            let server = tokio::spawn(async move {
+                let result =
+                    instance
+                        .server
+                        .run(sink, shutdown_trigger_recv, shutdown_send.clone());
+                if let Err(e) = result.await {
+                    log::error!("{e}");
+                    let _ = shutdown_send.send(()).await;
+                }
+            });
+            (name, server)
+        })
+        .collect();
+    drop(shutdown_send);
+
+    let health = Health::new(producer.clone(), cli.ready_max_queued_messages);
+    let reload = tokio::spawn(reload::run(
+        producer.clone(),
+        producer_metrics,
+        server_config,
+        shutdown_trigger_send.subscribe(),
+    ));
 
     let metrics = Metrics::new(registry)?;
     let prometheus = if let Some(addr) = cli.prometheus_address {
-        let prometheus = metrics.run(addr, shutdown_trigger_send.subscribe());
+        let prometheus = metrics.run(
+            addr,
+            prometheus_tls,
+            health,
+            shutdown_trigger_send.subscribe(),
+        );
         tokio::spawn(prometheus)
     } else {
         let mut r = shutdown_trigger_send.subscribe();
@@ -131,7 +217,8 @@ async fn run() -> Result<()> {
         result = shutdown_signal() => result?,
     }
     shutdown_trigger_send.send(())?;
-    server.await?;
+    reload.await??;
+    drain::run(producer, servers, Duration::from_secs(cli.drain_timeout)).await?;
     Ok(())
 }
 
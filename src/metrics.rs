@@ -30,6 +30,9 @@ use prometheus_client::registry::Registry;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast::Receiver;
 
+use crate::health::Health;
+use crate::tls::TlsAcceptor;
+
 pub const COLLECT_PERIOD_MS: u64 = 10000;
 
 pub struct Metrics {
@@ -59,17 +62,23 @@ impl Metrics {
     pub async fn run(
         self,
         bind_address: SocketAddr,
+        tls: Option<TlsAcceptor>,
+        health: Health,
         mut shutdown_trigger_receiver: Receiver<()>,
     ) -> Result<()> {
+        let health = Router::new()
+            .route("/health/live", get(Health::live_handler))
+            .route("/health/ready", get(Health::ready_handler))
+            .with_state(Arc::new(health));
         let app = Router::new()
             .route("/metrics", get(Metrics::metrics_handler))
-            .with_state(Arc::new(self));
+            .with_state(Arc::new(self))
+            .merge(health);
         let listener = TcpListener::bind(bind_address).await?;
-        axum::serve(listener, app)
-            .with_graceful_shutdown(async move {
-                let _ = shutdown_trigger_receiver.recv().await;
-            })
-            .await?;
+        crate::tls::serve(listener, tls, app, async move {
+            let _ = shutdown_trigger_receiver.recv().await;
+        })
+        .await?;
         Ok(())
     }
 }
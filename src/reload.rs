@@ -0,0 +1,93 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::broadcast::Receiver;
+use tokio::task::{JoinError, JoinSet};
+
+use crate::cli::ServerCommand;
+use crate::kafka::{KafkaProducer, ProducerHandle, ProducerMetrics};
+
+async fn reload(
+    producer: &ProducerHandle,
+    metrics: &ProducerMetrics,
+    server: &ServerCommand,
+) -> Result<(Arc<KafkaProducer>, Duration)> {
+    let mut cli = crate::cli::config::try_parse()?;
+    let topics = cli.server.topics();
+    cli.producer.preflight.preflight_topics.extend(topics);
+    if cli.server != *server {
+        log::warn!("Server configuration changes require a restart");
+    }
+
+    let previous = producer.replace(KafkaProducer::new(cli.producer, metrics).await?);
+    log::info!("Configuration reloaded, draining previous producer");
+    Ok((previous, Duration::from_secs(cli.drain_timeout)))
+}
+
+fn drained(result: Result<Result<()>, JoinError>) {
+    match result {
+        Ok(Ok(())) => log::info!("Drained previous producer"),
+        Ok(Err(e)) => log::warn!("Failed to drain previous producer: {e}"),
+        Err(e) => log::warn!("Failed to drain previous producer: {e}"),
+    }
+}
+
+#[cfg(unix)]
+pub async fn run(
+    producer: ProducerHandle,
+    metrics: ProducerMetrics,
+    server: ServerCommand,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    let mut drains = JoinSet::new();
+    loop {
+        tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            Some(result) = drains.join_next() => {
+                drained(result);
+                continue;
+            }
+            _ = hangup.recv() => (),
+        }
+        log::info!("Reloading configuration");
+        match reload(&producer, &metrics, &server).await {
+            Ok((previous, timeout)) => {
+                drains.spawn_blocking(move || previous.flush(timeout));
+            }
+            Err(e) => log::error!("Failed to reload configuration: {e}"),
+        }
+    }
+    while let Some(result) = drains.join_next().await {
+        drained(result);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn run(
+    _producer: ProducerHandle,
+    _metrics: ProducerMetrics,
+    _server: ServerCommand,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()> {
+    let _ = shutdown_trigger_receiver.recv().await;
+    Ok(())
+}
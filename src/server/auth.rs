@@ -0,0 +1,431 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, AlgorithmFamily, DecodingKey, Validation};
+use serde::Deserialize;
+
+use crate::ENGINE;
+use crate::cli::auth::Auth;
+use crate::tls::ClientSubject;
+
+const API_KEY_HEADER: &str = "x-api-key";
+const WILDCARD: &str = "*";
+
+pub enum Credentials {
+    ApiKey(String),
+    Basic(String, String),
+    Bearer(String),
+    Certificate(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Principal(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Unauthenticated,
+    Forbidden,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+struct Jwt {
+    keys: JwkSet,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+pub struct Authenticator {
+    api_keys: HashMap<String, String>,
+    users: HashMap<String, String>,
+    jwt: Option<Jwt>,
+    certificates: bool,
+    permissions: Option<HashMap<String, Vec<String>>>,
+}
+
+fn read_lines(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut entries = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((name, value)) => entries.push((name.to_string(), value.to_string())),
+            None => bail!("Invalid line in {}: {line}", path.display()),
+        }
+    }
+    Ok(entries)
+}
+
+// The accepted algorithms come from the key, never from the untrusted token header.
+fn validation(jwk: &Jwk) -> Option<Validation> {
+    if let Some(alg) = jwk.common.key_algorithm {
+        return Algorithm::try_from(alg).ok().map(Validation::new);
+    }
+    let family = match &jwk.algorithm {
+        AlgorithmParameters::OctetKey(_) => AlgorithmFamily::Hmac,
+        AlgorithmParameters::RSA(_) => AlgorithmFamily::Rsa,
+        AlgorithmParameters::EllipticCurve(params) => {
+            return match params.curve {
+                EllipticCurve::P256 => Some(Validation::new(Algorithm::ES256)),
+                EllipticCurve::P384 => Some(Validation::new(Algorithm::ES384)),
+                _ => None,
+            };
+        }
+        AlgorithmParameters::OctetKeyPair(_) => AlgorithmFamily::Ed,
+        _ => return None,
+    };
+    Some(Validation::new_for_family(family))
+}
+
+impl Rejection {
+    pub fn reason(self) -> &'static str {
+        match self {
+            Rejection::Unauthenticated => "unauthenticated",
+            Rejection::Forbidden => "forbidden",
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection::Unauthenticated => (
+                StatusCode::UNAUTHORIZED,
+                [(WWW_AUTHENTICATE, "Basic realm=\"kafka-proxy\"")],
+            )
+                .into_response(),
+            Rejection::Forbidden => StatusCode::FORBIDDEN.into_response(),
+        }
+    }
+}
+
+impl Credentials {
+    pub fn from_headers(headers: &HeaderMap, subject: Option<&ClientSubject>) -> Vec<Credentials> {
+        let mut credentials = Vec::new();
+        if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+            credentials.push(Credentials::ApiKey(key.to_string()));
+        }
+        if let Some(authorization) = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+            if let Some(token) = authorization.strip_prefix("Bearer ") {
+                credentials.push(Credentials::Bearer(token.trim().to_string()));
+            } else if let Some(basic) = authorization.strip_prefix("Basic ")
+                && let Ok(decoded) = ENGINE.decode(basic.trim())
+                && let Ok(decoded) = String::from_utf8(decoded)
+                && let Some((user, password)) = decoded.split_once(':')
+            {
+                credentials.push(Credentials::Basic(user.to_string(), password.to_string()));
+            }
+        }
+        if let Some(ClientSubject(subject)) = subject {
+            credentials.push(Credentials::Certificate(subject.clone()));
+        }
+        credentials
+    }
+
+    pub fn from_query<'a>(query: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<Credentials> {
+        let mut credentials = Vec::new();
+        let (mut user, mut password) = (None, None);
+        for (key, value) in query {
+            match key {
+                "api_key" => credentials.push(Credentials::ApiKey(value.to_string())),
+                "access_token" => credentials.push(Credentials::Bearer(value.to_string())),
+                "user" => user = Some(value),
+                "password" => password = Some(value),
+                _ => (),
+            }
+        }
+        if let (Some(user), Some(password)) = (user, password) {
+            credentials.push(Credentials::Basic(user.to_string(), password.to_string()));
+        }
+        credentials
+    }
+}
+
+impl Authenticator {
+    pub fn new(cfg: &Auth, certificates: bool) -> Result<Option<Arc<Authenticator>>> {
+        if !cfg.enabled() {
+            return Ok(None);
+        }
+
+        let mut api_keys = HashMap::new();
+        if let Some(path) = &cfg.api_keys {
+            for (principal, key) in read_lines(path)? {
+                api_keys.insert(key, principal);
+            }
+        }
+
+        let users = match &cfg.htpasswd {
+            None => HashMap::new(),
+            Some(path) => read_lines(path)?.into_iter().collect(),
+        };
+
+        let jwt = match &cfg.jwks {
+            None => None,
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                Some(Jwt {
+                    keys: serde_json::from_str(&content)
+                        .with_context(|| format!("Invalid JWKS in {}", path.display()))?,
+                    issuer: cfg.jwt_issuer.clone(),
+                    audience: cfg.jwt_audience.clone(),
+                })
+            }
+        };
+
+        let permissions = match &cfg.permissions {
+            None => None,
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                Some(
+                    toml::from_str(&content)
+                        .with_context(|| format!("Invalid permissions in {}", path.display()))?,
+                )
+            }
+        };
+
+        Ok(Some(Arc::new(Authenticator {
+            api_keys,
+            users,
+            jwt,
+            certificates,
+            permissions,
+        })))
+    }
+
+    fn verify_jwt(&self, token: &str) -> Option<String> {
+        let jwt = self.jwt.as_ref()?;
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        let jwk = match (&header.kid, jwt.keys.keys.as_slice()) {
+            (Some(kid), _) => jwt.keys.find(kid)?,
+            (None, [jwk]) => jwk,
+            (None, _) => return None,
+        };
+        let key = DecodingKey::from_jwk(jwk).ok()?;
+        let mut validation = validation(jwk)?;
+        validation.validate_aud = jwt.audience.is_some();
+        if let Some(audience) = &jwt.audience {
+            validation.set_audience(&[audience]);
+        }
+        if let Some(issuer) = &jwt.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match jsonwebtoken::decode::<Claims>(token, &key, &validation) {
+            Ok(token) => Some(token.claims.sub),
+            Err(e) => {
+                log::debug!("Invalid JWT: {e}");
+                None
+            }
+        }
+    }
+
+    fn authenticate(&self, credentials: &Credentials) -> Option<String> {
+        match credentials {
+            Credentials::ApiKey(key) => self.api_keys.get(key).cloned(),
+            Credentials::Basic(user, password) => {
+                let hash = self.users.get(user)?;
+                match bcrypt::verify(password, hash) {
+                    Ok(true) => Some(user.clone()),
+                    _ => None,
+                }
+            }
+            Credentials::Bearer(token) => self.verify_jwt(token),
+            Credentials::Certificate(subject) if self.certificates => Some(subject.clone()),
+            Credentials::Certificate(_) => None,
+        }
+    }
+
+    fn authorize(&self, principal: &str, topic: &str) -> bool {
+        let Some(permissions) = &self.permissions else {
+            return true;
+        };
+        [principal, WILDCARD]
+            .iter()
+            .filter_map(|principal| permissions.get(*principal))
+            .flatten()
+            .any(|allowed| allowed == topic || allowed == WILDCARD)
+    }
+
+    fn check_blocking(
+        &self,
+        credentials: &[Credentials],
+        topic: &str,
+    ) -> Result<String, Rejection> {
+        let principal = credentials
+            .iter()
+            .find_map(|credentials| self.authenticate(credentials))
+            .ok_or(Rejection::Unauthenticated)?;
+        if !self.authorize(&principal, topic) {
+            return Err(Rejection::Forbidden);
+        }
+        Ok(principal)
+    }
+
+    pub async fn check(
+        self: &Arc<Self>,
+        credentials: Vec<Credentials>,
+        topic: String,
+    ) -> Result<String, Rejection> {
+        let authenticator = self.clone();
+        let principal =
+            tokio::task::spawn_blocking(move || authenticator.check_blocking(&credentials, &topic))
+                .await
+                .map_err(|_| Rejection::Unauthenticated)??;
+        Ok(principal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Authenticator, Credentials, Rejection};
+    use crate::cli::auth::Auth;
+    use crate::server::tests::temp_path;
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn test_authenticator() {
+        let api_keys = temp_path("api-keys");
+        std::fs::write(&api_keys, "# comment\nservice:secret-key\n").unwrap();
+        let htpasswd = temp_path("htpasswd");
+        let hash = bcrypt::hash("password", 4).unwrap();
+        std::fs::write(&htpasswd, format!("alice:{hash}\n")).unwrap();
+        let jwks = temp_path("jwks.json");
+        let jwk = json!({"keys": [
+            {"kty": "oct", "kid": "test", "alg": "HS256", "k": URL_SAFE_NO_PAD.encode(b"jwt-secret")},
+            {"kty": "oct", "kid": "family", "k": URL_SAFE_NO_PAD.encode(b"family-secret")},
+        ]});
+        std::fs::write(&jwks, jwk.to_string()).unwrap();
+        let permissions = temp_path("permissions.toml");
+        std::fs::write(
+            &permissions,
+            "service = [\"*\"]\nalice = [\"events\"]\n\"*\" = [\"public\"]\n",
+        )
+        .unwrap();
+
+        let authenticator = Authenticator::new(
+            &Auth {
+                api_keys: Some(api_keys),
+                htpasswd: Some(htpasswd),
+                jwks: Some(jwks),
+                jwt_issuer: Some("issuer".to_string()),
+                jwt_audience: None,
+                permissions: Some(permissions),
+            },
+            false,
+        )
+        .unwrap()
+        .unwrap();
+        let check = |credentials: Credentials, topic: &str| {
+            let authenticator = authenticator.clone();
+            let topic = topic.to_string();
+            async move { authenticator.check(vec![credentials], topic).await }
+        };
+
+        let key = || Credentials::ApiKey("secret-key".to_string());
+        assert_eq!(check(key(), "other").await, Ok("service".to_string()));
+        let wrong_key = Credentials::ApiKey("wrong".to_string());
+        assert_eq!(
+            check(wrong_key, "events").await,
+            Err(Rejection::Unauthenticated)
+        );
+
+        let basic = |password: &str| Credentials::Basic("alice".to_string(), password.to_string());
+        assert_eq!(
+            check(basic("password"), "events").await,
+            Ok("alice".to_string())
+        );
+        assert_eq!(
+            check(basic("password"), "public").await,
+            Ok("alice".to_string())
+        );
+        assert_eq!(
+            check(basic("password"), "other").await,
+            Err(Rejection::Forbidden)
+        );
+        assert_eq!(
+            check(basic("wrong"), "events").await,
+            Err(Rejection::Unauthenticated)
+        );
+
+        let exp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 60;
+        let signed = |kid: Option<&str>, alg: Algorithm, secret: &[u8], iss: &str| {
+            let header = Header {
+                kid: kid.map(str::to_string),
+                ..Header::new(alg)
+            };
+            let claims = json!({"sub": "bob", "iss": iss, "exp": exp});
+            let token =
+                jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap();
+            Credentials::Bearer(token)
+        };
+        let token = |iss: &str| signed(Some("test"), Algorithm::HS256, b"jwt-secret", iss);
+        assert_eq!(
+            check(token("issuer"), "public").await,
+            Ok("bob".to_string())
+        );
+        assert_eq!(
+            check(token("issuer"), "events").await,
+            Err(Rejection::Forbidden)
+        );
+        assert_eq!(
+            check(token("other"), "public").await,
+            Err(Rejection::Unauthenticated)
+        );
+        let downgraded = signed(Some("test"), Algorithm::HS384, b"jwt-secret", "issuer");
+        assert_eq!(
+            check(downgraded, "public").await,
+            Err(Rejection::Unauthenticated)
+        );
+        let family = signed(Some("family"), Algorithm::HS384, b"family-secret", "issuer");
+        assert_eq!(check(family, "public").await, Ok("bob".to_string()));
+        let without_kid = signed(None, Algorithm::HS256, b"jwt-secret", "issuer");
+        assert_eq!(
+            check(without_kid, "public").await,
+            Err(Rejection::Unauthenticated)
+        );
+
+        let certificate = Credentials::Certificate("CN=client".to_string());
+        assert_eq!(
+            check(certificate, "public").await,
+            Err(Rejection::Unauthenticated)
+        );
+    }
+}
@@ -14,38 +14,102 @@
  * limitations under the License.
  */
 
-use crate::cli::CoapServer;
-use crate::kafka::KafkaProducer;
-use crate::server::Server;
+use crate::cli::{AckMode, CoapServer};
+use crate::server::auth::{Authenticator, Credentials, Rejection};
+use crate::server::rate_limit::RateLimiter;
+use crate::server::{Message, Server, SharedSink};
 use async_trait::async_trait;
-use coap::request::{CoapRequest, Method, Status};
+use coap::request::{CoapOption, CoapRequest, Method, Status};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::mpsc::Sender;
 
+async fn produce(
+    sink: &SharedSink,
+    authenticator: Option<&Arc<Authenticator>>,
+    rate_limiter: Option<&Arc<RateLimiter>>,
+    ack_mode: AckMode,
+    request: &mut CoapRequest<SocketAddr>,
+) -> Status {
+    let mut principal = None;
+    if let Some(authenticator) = authenticator {
+        let query = request
+            .message
+            .get_option(CoapOption::UriQuery)
+            .into_iter()
+            .flatten()
+            .filter_map(|query| std::str::from_utf8(query).ok())
+            .filter_map(|query| query.split_once('='));
+        let credentials = Credentials::from_query(query);
+        match authenticator.check(credentials, sink.topic()).await {
+            Ok(p) => principal = Some(p),
+            Err(rejection) => {
+                sink.rejected(rejection.reason());
+                return match rejection {
+                    Rejection::Unauthenticated => Status::Unauthorized,
+                    Rejection::Forbidden => Status::Forbidden,
+                };
+            }
+        }
+    }
+    if let Some(rate_limiter) = rate_limiter
+        && let Err(retry_after) =
+            rate_limiter.check(request.source.map(|s| s.ip()), principal.as_deref())
+    {
+        sink.rejected("rate_limited");
+        if let Some(ref mut response) = request.response {
+            let max_age = retry_after.as_secs_f64().ceil() as u32;
+            let max_age = max_age.to_be_bytes();
+            let start = max_age
+                .iter()
+                .position(|b| *b != 0)
+                .unwrap_or(max_age.len());
+            response
+                .message
+                .add_option(CoapOption::MaxAge, max_age[start..].to_vec());
+        }
+        return Status::ServiceUnavailable;
+    }
+    let message = Message::from(request.message.payload.as_slice());
+    match sink.send_with_ack(message, ack_mode).await {
+        Ok(_) => Status::Changed,
+        Err(e) => {
+            log::warn!("{e}");
+            Status::InternalServerError
+        }
+    }
+}
+
 #[async_trait]
 impl Server for CoapServer {
     async fn run(
         &self,
-        kafka_producer: KafkaProducer,
+        sink: SharedSink,
         mut shutdown_trigger_receiver: Receiver<()>,
         _shutdown_sender: Sender<()>,
     ) -> anyhow::Result<()> {
-        let kafka_producer = Arc::new(kafka_producer);
+        let authenticator = Authenticator::new(&self.auth, false)?;
+        let rate_limiter = RateLimiter::with_principals(&self.rate_limit);
+        let ack_mode = self.ack_mode;
         let server = coap::Server::new_udp(self.address)?;
         let run = server.run(move |mut request: Box<CoapRequest<SocketAddr>>| {
-            let kafka_producer = kafka_producer.clone();
+            let sink = sink.clone();
+            let authenticator = authenticator.clone();
+            let rate_limiter = rate_limiter.clone();
             async move {
                 let response_status = match request.get_method() {
                     &Method::Post => match request.get_path().as_str() {
-                        "produce" => match kafka_producer.send(&request.message.payload).await {
-                            Ok(()) => Status::Changed,
-                            Err(e) => {
-                                log::warn!("{e}");
-                                Status::InternalServerError
-                            }
-                        },
+                        "produce" => {
+                            produce(
+                                &sink,
+                                authenticator.as_ref(),
+                                rate_limiter.as_ref(),
+                                ack_mode,
+                                &mut request,
+                            )
+                            .await
+                        }
                         _ => Status::NotFound,
                     },
                     _ => Status::MethodNotAllowed,
@@ -66,3 +130,39 @@ impl Server for CoapServer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{AckMode, Auth, CoapServer, PrincipalRateLimit};
+    use crate::server::tests::{RunningServer, free_udp_address, retry};
+    use coap::client::UdpCoAPClient;
+    use coap::request::Status;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_produce() {
+        let address = free_udp_address();
+        let server = RunningServer::start(CoapServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit::default(),
+        });
+
+        let url = format!("coap://{address}/produce");
+        let response = retry(|| async {
+            let response = UdpCoAPClient::post_with_timeout(
+                &url,
+                b"message".to_vec(),
+                Duration::from_millis(100),
+            )
+            .await?;
+            Ok(response)
+        })
+        .await;
+        assert_eq!(response.get_status(), &Status::Changed);
+        assert_eq!(server.sink.wait_for(1).await[0], b"message".to_vec());
+
+        server.stop().await;
+    }
+}
@@ -0,0 +1,509 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Read;
+
+use anyhow::{Context as _, Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat};
+use flate2::read::MultiGzDecoder;
+use futures::StreamExt;
+use rmpv::Value as MsgPack;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc::Sender;
+
+use crate::cli::ForwardServer;
+use crate::server::{Message, Server, SharedSink, backpressure};
+use crate::tls::{ClientSubject, TlsAcceptor, serve_streams};
+
+const EVENT_TIME: i8 = 0;
+
+struct Event {
+    topic: String,
+    payload: Vec<u8>,
+}
+
+struct EventStream {
+    events: Vec<Event>,
+    chunk: Option<MsgPack>,
+}
+
+#[derive(Clone)]
+struct Context {
+    sink: SharedSink,
+    concurrency_limit: usize,
+    max_message_size: usize,
+    topic_prefix: Option<String>,
+    time_key: Option<String>,
+    subject_header: Option<String>,
+}
+
+fn length(buf: &[u8], pos: usize, size: usize) -> Option<usize> {
+    let bytes = buf.get(pos..pos + size)?;
+    Some(bytes.iter().fold(0, |len, b| (len << 8) | *b as usize))
+}
+
+fn message_len(buf: &[u8]) -> Result<Option<usize>> {
+    let mut pos = 0;
+    let mut remaining: usize = 1;
+    while remaining > 0 {
+        remaining -= 1;
+        let Some(&marker) = buf.get(pos) else {
+            return Ok(None);
+        };
+        pos += 1;
+        let (data, items) = match marker {
+            0x00..=0x7f | 0xc0 | 0xc2 | 0xc3 | 0xe0..=0xff => (0, 0),
+            0x80..=0x8f => (0, 2 * (marker & 0x0f) as usize),
+            0x90..=0x9f => (0, (marker & 0x0f) as usize),
+            0xa0..=0xbf => ((marker & 0x1f) as usize, 0),
+            0xcc | 0xd0 => (1, 0),
+            0xcd | 0xd1 => (2, 0),
+            0xca | 0xce | 0xd2 => (4, 0),
+            0xcb | 0xcf | 0xd3 => (8, 0),
+            0xd4 => (2, 0),
+            0xd5 => (3, 0),
+            0xd6 => (5, 0),
+            0xd7 => (9, 0),
+            0xd8 => (17, 0),
+            0xc4 | 0xc5 | 0xc6 | 0xc7 | 0xc8 | 0xc9 | 0xd9 | 0xda | 0xdb => {
+                let size = match marker {
+                    0xc4 | 0xc7 | 0xd9 => 1,
+                    0xc5 | 0xc8 | 0xda => 2,
+                    _ => 4,
+                };
+                let Some(len) = length(buf, pos, size) else {
+                    return Ok(None);
+                };
+                pos += size;
+                let ext_type = matches!(marker, 0xc7..=0xc9) as usize;
+                (len + ext_type, 0)
+            }
+            0xdc..=0xdf => {
+                let size = if marker & 1 == 0 { 2 } else { 4 };
+                let Some(len) = length(buf, pos, size) else {
+                    return Ok(None);
+                };
+                pos += size;
+                (0, if marker >= 0xde { 2 * len } else { len })
+            }
+            0xc1 => bail!("Invalid MessagePack marker"),
+        };
+        pos += data;
+        remaining = remaining.saturating_add(items);
+    }
+    Ok((pos <= buf.len()).then_some(pos))
+}
+
+async fn read_message<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max_message_size: usize,
+) -> Result<Option<MsgPack>>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        if let Some(len) = message_len(buf)? {
+            let message = rmpv::decode::read_value(&mut &buf[..len])?;
+            buf.drain(..len);
+            return Ok(Some(message));
+        }
+        if buf.len() >= max_message_size {
+            bail!("Message exceeds {max_message_size} bytes");
+        }
+        buf.reserve(8192);
+        if reader.read_buf(buf).await? == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            bail!("Connection closed within a message");
+        }
+    }
+}
+
+fn json(value: MsgPack) -> Value {
+    match value {
+        MsgPack::Nil | MsgPack::Ext(_, _) => Value::Null,
+        MsgPack::Boolean(b) => b.into(),
+        MsgPack::Integer(i) => match (i.as_i64(), i.as_u64()) {
+            (Some(i), _) => i.into(),
+            (_, Some(u)) => u.into(),
+            _ => Value::Null,
+        },
+        MsgPack::F32(f) => f.into(),
+        MsgPack::F64(f) => f.into(),
+        MsgPack::String(s) => String::from_utf8_lossy(s.as_bytes()).into(),
+        MsgPack::Binary(b) => String::from_utf8_lossy(&b).into(),
+        MsgPack::Array(values) => values.into_iter().map(json).collect(),
+        MsgPack::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        MsgPack::String(s) => String::from_utf8_lossy(s.as_bytes()).into_owned(),
+                        key => key.to_string(),
+                    };
+                    (key, json(value))
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn time(value: &MsgPack) -> Option<String> {
+    let (secs, nanos) = match value {
+        MsgPack::Integer(secs) => (secs.as_i64()?, 0),
+        MsgPack::F64(secs) => (secs.trunc() as i64, (secs.fract() * 1e9) as u32),
+        MsgPack::Ext(EVENT_TIME, data) if data.len() == 8 => (
+            u32::from_be_bytes(data[..4].try_into().ok()?) as i64,
+            u32::from_be_bytes(data[4..].try_into().ok()?),
+        ),
+        _ => return None,
+    };
+    let time = DateTime::from_timestamp(secs, nanos)?;
+    Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+fn option<'a>(options: Option<&'a MsgPack>, key: &str) -> Option<&'a MsgPack> {
+    options?
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, value)| value)
+}
+
+impl Context {
+    fn event(&self, tag: &str, time: &MsgPack, record: MsgPack) -> Result<Event> {
+        let mut record = json(record);
+        if let (Some(time_key), Some(time), Value::Object(record)) =
+            (&self.time_key, self::time(time), &mut record)
+        {
+            record.insert(time_key.clone(), time.into());
+        }
+        Ok(Event {
+            topic: format!("{}{tag}", self.topic_prefix.as_deref().unwrap_or_default()),
+            payload: serde_json::to_vec(&record)?,
+        })
+    }
+
+    fn entry(&self, tag: &str, entry: MsgPack) -> Result<Event> {
+        let MsgPack::Array(entry) = entry else {
+            bail!("Expected [time, record] entry");
+        };
+        let mut entry = entry.into_iter();
+        let (Some(time), Some(record)) = (entry.next(), entry.next()) else {
+            bail!("Expected [time, record] entry");
+        };
+        self.event(tag, &time, record)
+    }
+
+    fn packed(&self, tag: &str, entries: &[u8], options: Option<&MsgPack>) -> Result<Vec<Event>> {
+        let decompressed;
+        let mut entries = match option(options, "compressed").and_then(MsgPack::as_str) {
+            None | Some("text") => entries,
+            Some("gzip") => {
+                let mut buf = Vec::new();
+                MultiGzDecoder::new(entries)
+                    .take(self.max_message_size as u64 + 1)
+                    .read_to_end(&mut buf)?;
+                if buf.len() > self.max_message_size {
+                    bail!(
+                        "Decompressed entries exceed {} bytes",
+                        self.max_message_size
+                    );
+                }
+                decompressed = buf;
+                &decompressed
+            }
+            Some(compression) => bail!("Unsupported compression {compression}"),
+        };
+        let mut events = Vec::new();
+        while !entries.is_empty() {
+            let entry = rmpv::decode::read_value(&mut entries)?;
+            events.push(self.entry(tag, entry)?);
+        }
+        Ok(events)
+    }
+
+    fn decode(&self, message: MsgPack) -> Result<EventStream> {
+        let MsgPack::Array(message) = message else {
+            bail!("Expected an array");
+        };
+        let mut message = message.into_iter();
+        let tag = message.next().context("Missing tag")?;
+        let tag = tag.as_str().context("Invalid tag")?;
+        let (events, options) = match message.next().context("Missing entries")? {
+            MsgPack::Array(entries) => {
+                let events = entries
+                    .into_iter()
+                    .map(|entry| self.entry(tag, entry))
+                    .collect::<Result<_>>()?;
+                (events, message.next())
+            }
+            MsgPack::Binary(entries) => {
+                let options = message.next();
+                (self.packed(tag, &entries, options.as_ref())?, options)
+            }
+            MsgPack::String(entries) => {
+                let options = message.next();
+                (
+                    self.packed(tag, entries.as_bytes(), options.as_ref())?,
+                    options,
+                )
+            }
+            time => {
+                let record = message.next().context("Missing record")?;
+                (vec![self.event(tag, &time, record)?], message.next())
+            }
+        };
+        Ok(EventStream {
+            events,
+            chunk: option(options.as_ref(), "chunk").cloned(),
+        })
+    }
+
+    async fn produce(&self, events: Vec<Event>, subject: Option<&str>) -> bool {
+        futures::stream::iter(events)
+            .map(|event| async move {
+                let mut headers = Vec::new();
+                if let (Some(header), Some(subject)) = (&self.subject_header, subject) {
+                    headers.push((header.as_str(), subject.as_bytes()));
+                }
+                let message = Message {
+                    topic: Some(&event.topic),
+                    key: None,
+                    headers,
+                    payload: &event.payload,
+                };
+                match self.sink.send_message(message).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        log::warn!("{e}");
+                        false
+                    }
+                }
+            })
+            .buffer_unordered(self.concurrency_limit.max(1))
+            .fold(true, |all, delivered| async move { all && delivered })
+            .await
+    }
+}
+
+async fn connection<S>(
+    mut stream: S,
+    subject: Option<ClientSubject>,
+    context: Context,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let subject = subject.map(|ClientSubject(subject)| subject);
+    let mut buf = Vec::new();
+    loop {
+        let message = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            message = async {
+                backpressure(&context.sink).await;
+                read_message(&mut stream, &mut buf, context.max_message_size).await
+            } => message?,
+        };
+        let Some(message) = message else {
+            break;
+        };
+        let EventStream { events, chunk } = context.decode(message)?;
+        let delivered = context.produce(events, subject.as_deref()).await;
+        if let (true, Some(chunk)) = (delivered, chunk) {
+            let ack = MsgPack::Map(vec![(MsgPack::from("ack"), chunk)]);
+            let mut reply = Vec::new();
+            rmpv::encode::write_value(&mut reply, &ack)?;
+            stream.write_all(&reply).await?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Server for ForwardServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let context = Context {
+            sink,
+            concurrency_limit: self.concurrency_limit,
+            max_message_size: self.max_message_size,
+            topic_prefix: self.topic_prefix.clone(),
+            time_key: self.time_key.clone(),
+            subject_header: self.tls.tls_subject_header.clone(),
+        };
+        let acceptor = TlsAcceptor::new(&self.tls)?;
+        let listener = TcpListener::bind(&self.address).await?;
+        serve_streams(
+            listener,
+            acceptor,
+            shutdown_trigger_receiver,
+            move |stream, subject, shutdown_trigger_receiver| {
+                connection(stream, subject, context.clone(), shutdown_trigger_receiver)
+            },
+        )
+        .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::message_len;
+    use crate::cli::{ForwardServer, Tls};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry};
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use rmpv::Value as MsgPack;
+    use serde_json::{Value, json};
+    use std::io::Write;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    fn encode(value: &MsgPack) -> Vec<u8> {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, value).unwrap();
+        buf
+    }
+
+    fn record(message: &str) -> MsgPack {
+        MsgPack::Map(vec![(MsgPack::from("log"), MsgPack::from(message))])
+    }
+
+    fn entry(message: &str) -> MsgPack {
+        MsgPack::Array(vec![
+            MsgPack::Ext(0, vec![0, 0, 0, 1, 0, 0, 0, 2]),
+            record(message),
+        ])
+    }
+
+    fn chunk(id: &str) -> MsgPack {
+        MsgPack::Map(vec![(MsgPack::from("chunk"), MsgPack::from(id))])
+    }
+
+    #[test]
+    fn test_message_len() {
+        let message = encode(&MsgPack::Array(vec![
+            MsgPack::from("tag"),
+            MsgPack::Binary(vec![0; 300]),
+            MsgPack::Map(vec![(MsgPack::from(1), MsgPack::F64(1.5))]),
+        ]));
+        for len in 0..message.len() {
+            assert_eq!(message_len(&message[..len]).unwrap(), None);
+        }
+        assert_eq!(message_len(&message).unwrap(), Some(message.len()));
+        let mut pipelined = message.clone();
+        pipelined.push(0x90);
+        assert_eq!(message_len(&pipelined).unwrap(), Some(message.len()));
+    }
+
+    #[tokio::test]
+    async fn test_forward() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(ForwardServer {
+            address,
+            concurrency_limit: 16,
+            max_message_size: 1024 * 1024,
+            topic_prefix: Some("logs.".to_string()),
+            time_key: Some("time".to_string()),
+            tls: Tls::default(),
+        });
+
+        let mut stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+        let message = MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::from(1),
+            record("message"),
+        ]);
+        let forward = MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::Array(vec![entry("forward")]),
+        ]);
+        let packed = MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::Binary(encode(&entry("packed"))),
+            chunk("packed"),
+        ]);
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&encode(&entry("compressed"))).unwrap();
+        let compressed = MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::Binary(gzip.finish().unwrap()),
+            MsgPack::Map(vec![
+                (MsgPack::from("chunk"), MsgPack::from("compressed")),
+                (MsgPack::from("compressed"), MsgPack::from("gzip")),
+            ]),
+        ]);
+        for message in [message, forward, packed, compressed] {
+            stream.write_all(&encode(&message)).await.unwrap();
+        }
+
+        let mut acks = Vec::new();
+        let mut buf = [0; 64];
+        let acks = loop {
+            let len = stream.read(&mut buf).await.unwrap();
+            assert_ne!(len, 0);
+            acks.extend_from_slice(&buf[..len]);
+            let mut acks = acks.as_slice();
+            let decoded = (
+                rmpv::decode::read_value(&mut acks),
+                rmpv::decode::read_value(&mut acks),
+            );
+            if let (Ok(first), Ok(second)) = decoded {
+                break [first, second];
+            }
+        };
+        assert_eq!(
+            acks,
+            ["packed", "compressed"]
+                .map(|id| MsgPack::Map(vec![(MsgPack::from("ack"), MsgPack::from(id))]))
+        );
+
+        let messages: Vec<Value> = server
+            .sink
+            .messages()
+            .await
+            .iter()
+            .map(|message| serde_json::from_slice(message).unwrap())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                json!({"log": "message", "time": "1970-01-01T00:00:01Z"}),
+                json!({"log": "forward", "time": "1970-01-01T00:00:01.000000002Z"}),
+                json!({"log": "packed", "time": "1970-01-01T00:00:01.000000002Z"}),
+                json!({"log": "compressed", "time": "1970-01-01T00:00:01.000000002Z"}),
+            ]
+        );
+        assert_eq!(
+            server.sink.topics().await,
+            vec![Some("logs.app".to_string()); 4]
+        );
+
+        server.stop().await;
+    }
+}
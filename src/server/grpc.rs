@@ -0,0 +1,271 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use futures::{StreamExt, TryStreamExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc::Sender;
+use tonic::server::Grpc;
+use tonic::{Extensions, Status, Streaming};
+use tonic_prost::ProstCodec;
+
+use crate::cli::{AckMode, GrpcServer};
+use crate::server::auth::Rejection;
+use crate::server::{Message, Server, SharedSink};
+use crate::tls::{ClientSubject, TlsAcceptor};
+
+const PRODUCE: &str = "/kafka_proxy.Producer/Produce";
+const PRODUCE_STREAM: &str = "/kafka_proxy.Producer/ProduceStream";
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Record {
+    #[prost(string, optional, tag = "1")]
+    topic: Option<String>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    key: Option<Vec<u8>>,
+    #[prost(message, repeated, tag = "3")]
+    headers: Vec<Header>,
+    #[prost(bytes = "vec", tag = "4")]
+    payload: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Header {
+    #[prost(string, tag = "1")]
+    key: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RecordMetadata {
+    #[prost(int32, optional, tag = "1")]
+    partition: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    offset: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProduceStreamResponse {
+    #[prost(message, repeated, tag = "1")]
+    records: Vec<RecordMetadata>,
+}
+
+#[derive(Clone)]
+struct GrpcState {
+    sink: SharedSink,
+    subject_header: Option<String>,
+    concurrency_limit: usize,
+}
+
+fn subject(extensions: &Extensions) -> Option<String> {
+    extensions
+        .get::<ClientSubject>()
+        .map(|ClientSubject(subject)| subject.clone())
+}
+
+async fn produce(
+    state: &GrpcState,
+    subject: Option<&str>,
+    record: Record,
+) -> std::result::Result<RecordMetadata, Status> {
+    if state.sink.saturated() {
+        state.sink.rejected("backpressure");
+        return Err(Status::unavailable("backpressure"));
+    }
+    if let Some(topic) = &record.topic
+        && !state.sink.allowed(topic)
+    {
+        state.sink.rejected(Rejection::Forbidden.reason());
+        return Err(Status::permission_denied(format!(
+            "Producing to topic {topic} is not allowed"
+        )));
+    }
+    let mut headers: Vec<(&str, &[u8])> = record
+        .headers
+        .iter()
+        .map(|header| (header.key.as_str(), header.value.as_slice()))
+        .collect();
+    if let (Some(header), Some(subject)) = (&state.subject_header, subject) {
+        headers.push((header.as_str(), subject.as_bytes()));
+    }
+    let message = Message {
+        topic: record.topic.as_deref(),
+        key: record.key.as_deref(),
+        headers,
+        payload: &record.payload,
+    };
+    match state.sink.send_with_ack(message, AckMode::Delivered).await {
+        Ok(offset) => Ok(RecordMetadata {
+            partition: offset.map(|offset| offset.partition),
+            offset: offset.map(|offset| offset.offset),
+        }),
+        Err(e) => {
+            log::warn!("{e}");
+            Err(Status::internal(e.to_string()))
+        }
+    }
+}
+
+async fn produce_handler(State(state): State<GrpcState>, request: Request) -> Response {
+    let service = tower::service_fn(move |request: tonic::Request<Record>| {
+        let state = state.clone();
+        async move {
+            let subject = subject(request.extensions());
+            let metadata = produce(&state, subject.as_deref(), request.into_inner()).await?;
+            Ok(tonic::Response::new(metadata))
+        }
+    });
+    Grpc::new(ProstCodec::default())
+        .unary(service, request)
+        .await
+        .into_response()
+}
+
+async fn produce_stream_handler(State(state): State<GrpcState>, request: Request) -> Response {
+    let service = tower::service_fn(move |request: tonic::Request<Streaming<Record>>| {
+        let state = state.clone();
+        async move {
+            let subject = subject(request.extensions());
+            let records = request
+                .into_inner()
+                .map(|record| {
+                    let state = state.clone();
+                    let subject = subject.clone();
+                    async move { produce(&state, subject.as_deref(), record?).await }
+                })
+                .buffered(state.concurrency_limit.max(1))
+                .try_collect()
+                .await?;
+            Ok(tonic::Response::new(ProduceStreamResponse { records }))
+        }
+    });
+    Grpc::new(ProstCodec::default())
+        .client_streaming(service, request)
+        .await
+        .into_response()
+}
+
+#[async_trait]
+impl Server for GrpcServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        mut shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let state = GrpcState {
+            sink,
+            subject_header: self.tls.tls_subject_header.clone(),
+            concurrency_limit: self.concurrency_limit,
+        };
+        let app = Router::new()
+            .route(PRODUCE, post(produce_handler))
+            .route(PRODUCE_STREAM, post(produce_stream_handler))
+            .fallback(|| async {
+                Status::unimplemented("Unknown method").into_http::<axum::body::Body>()
+            })
+            .with_state(state);
+        let tls = TlsAcceptor::http(&self.tls)?;
+        let listener = TcpListener::bind(&self.address).await?;
+        crate::tls::serve(listener, tls, app, async move {
+            let _ = shutdown_trigger_receiver.recv().await;
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PRODUCE, PRODUCE_STREAM, ProduceStreamResponse, Record, RecordMetadata};
+    use crate::cli::{GrpcServer, Tls};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry};
+    use axum::http::uri::PathAndQuery;
+    use tonic::client::Grpc;
+    use tonic::transport::Channel;
+    use tonic_prost::ProstCodec;
+
+    fn record(payload: &str) -> Record {
+        Record {
+            topic: None,
+            key: Some(b"key".to_vec()),
+            headers: Vec::new(),
+            payload: payload.as_bytes().to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_produce() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(GrpcServer {
+            address,
+            concurrency_limit: 16,
+            tls: Tls::default(),
+        });
+
+        let channel = retry(|| async {
+            Ok(Channel::from_shared(format!("http://{address}"))?
+                .connect()
+                .await?)
+        })
+        .await;
+        let mut client = Grpc::new(channel);
+        client.ready().await.unwrap();
+        let response: tonic::Response<RecordMetadata> = client
+            .unary(
+                tonic::Request::new(record("first")),
+                PathAndQuery::from_static(PRODUCE),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.into_inner(),
+            RecordMetadata {
+                partition: Some(0),
+                offset: Some(0)
+            }
+        );
+
+        client.ready().await.unwrap();
+        let records = tokio_stream::iter(vec![record("second"), record("third")]);
+        let response: tonic::Response<ProduceStreamResponse> = client
+            .client_streaming(
+                tonic::Request::new(records),
+                PathAndQuery::from_static(PRODUCE_STREAM),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().records.len(), 2);
+        let mut messages = server.sink.messages().await;
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+        assert_eq!(server.sink.keys().await, vec![Some(b"key".to_vec()); 3]);
+
+        server.stop().await;
+    }
+}
@@ -0,0 +1,634 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod protocol;
+mod records;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use anyhow::{Context as _, Result, bail};
+use async_trait::async_trait;
+use bytes::BufMut;
+use futures::StreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+use crate::cli::{AckMode, KafkaServer};
+use crate::server::kafka::protocol::{
+    Reader, put_nullable_string, put_string, put_unsigned_varint,
+};
+use crate::server::kafka::records::{Invalid, Record};
+use crate::server::{Message, Server, SharedSink, backpressure};
+use crate::tls::{ClientSubject, TlsAcceptor, serve_streams};
+
+const PRODUCE: i16 = 0;
+const METADATA: i16 = 3;
+const API_VERSIONS: i16 = 18;
+const INIT_PRODUCER_ID: i16 = 22;
+const APIS: [(i16, i16, i16); 4] = [
+    (PRODUCE, 3, 8),
+    (METADATA, 0, 8),
+    (API_VERSIONS, 0, 3),
+    (INIT_PRODUCER_ID, 0, 1),
+];
+
+const NONE: i16 = 0;
+const UNKNOWN_SERVER_ERROR: i16 = -1;
+const CORRUPT_MESSAGE: i16 = 2;
+const UNSUPPORTED_VERSION: i16 = 35;
+const TRANSACTIONAL_ID_AUTHORIZATION_FAILED: i16 = 53;
+const UNSUPPORTED_COMPRESSION_TYPE: i16 = 76;
+const INVALID_RECORD: i16 = 87;
+
+const NODE_ID: i32 = 0;
+const CLUSTER_ID: &str = "kafka-proxy";
+
+enum Pending {
+    Ready(Vec<u8>),
+    Produce(JoinHandle<Vec<u8>>),
+    Unacknowledged(JoinHandle<Vec<u8>>),
+}
+
+struct Partition {
+    index: i32,
+    records: Result<Vec<Record>, Invalid>,
+}
+
+struct Topic {
+    name: String,
+    partitions: Vec<Partition>,
+}
+
+struct Produced {
+    error_code: i16,
+    error_message: Option<String>,
+}
+
+#[derive(Clone)]
+struct Context {
+    sink: SharedSink,
+    concurrency_limit: usize,
+    max_message_size: usize,
+    host: String,
+    port: i32,
+    subject_header: Option<String>,
+    producer_ids: Arc<AtomicI64>,
+}
+
+fn api_versions(version: i16) -> Vec<u8> {
+    let (error_code, version) = match version {
+        0..=3 => (NONE, version),
+        _ => (UNSUPPORTED_VERSION, 0),
+    };
+    let mut body = Vec::new();
+    body.put_i16(error_code);
+    if version >= 3 {
+        put_unsigned_varint(&mut body, APIS.len() as u64 + 1);
+    } else {
+        body.put_i32(APIS.len() as i32);
+    }
+    for (api_key, min_version, max_version) in APIS {
+        body.put_i16(api_key);
+        body.put_i16(min_version);
+        body.put_i16(max_version);
+        if version >= 3 {
+            body.put_u8(0);
+        }
+    }
+    if version >= 1 {
+        body.put_i32(0);
+    }
+    if version >= 3 {
+        body.put_u8(0);
+    }
+    body
+}
+
+fn init_producer_id(reader: &mut Reader, producer_ids: &AtomicI64) -> Result<Vec<u8>> {
+    let transactional_id = reader.nullable_string()?;
+    let mut body = Vec::new();
+    body.put_i32(0);
+    match transactional_id {
+        Some(_) => {
+            body.put_i16(TRANSACTIONAL_ID_AUTHORIZATION_FAILED);
+            body.put_i64(-1);
+            body.put_i16(-1);
+        }
+        None => {
+            body.put_i16(NONE);
+            body.put_i64(producer_ids.fetch_add(1, Ordering::Relaxed));
+            body.put_i16(0);
+        }
+    }
+    Ok(body)
+}
+
+impl Produced {
+    fn error(error_code: i16, error_message: String) -> Produced {
+        Produced {
+            error_code,
+            error_message: Some(error_message),
+        }
+    }
+}
+
+impl Context {
+    fn metadata(&self, version: i16, reader: &mut Reader) -> Result<Vec<u8>> {
+        let topics = match reader.array_len()? {
+            None => vec![self.sink.topic()],
+            Some(0) if version == 0 => vec![self.sink.topic()],
+            Some(len) => (0..len)
+                .map(|_| Ok(reader.string()?.to_string()))
+                .collect::<Result<_>>()?,
+        };
+
+        let mut body = Vec::new();
+        if version >= 3 {
+            body.put_i32(0);
+        }
+        body.put_i32(1);
+        body.put_i32(NODE_ID);
+        put_string(&mut body, &self.host);
+        body.put_i32(self.port);
+        if version >= 1 {
+            put_nullable_string(&mut body, None);
+        }
+        if version >= 2 {
+            put_nullable_string(&mut body, Some(CLUSTER_ID));
+        }
+        if version >= 1 {
+            body.put_i32(NODE_ID);
+        }
+        body.put_i32(topics.len() as i32);
+        for topic in &topics {
+            body.put_i16(NONE);
+            put_string(&mut body, topic);
+            if version >= 1 {
+                body.put_u8(0);
+            }
+            body.put_i32(1);
+            body.put_i16(NONE);
+            body.put_i32(0);
+            body.put_i32(NODE_ID);
+            if version >= 7 {
+                body.put_i32(0);
+            }
+            for _ in 0..2 {
+                body.put_i32(1);
+                body.put_i32(NODE_ID);
+            }
+            if version >= 5 {
+                body.put_i32(0);
+            }
+            if version >= 8 {
+                body.put_i32(i32::MIN);
+            }
+        }
+        if version >= 8 {
+            body.put_i32(i32::MIN);
+        }
+        Ok(body)
+    }
+
+    async fn send(&self, topic: &str, partition: Partition, subject: Option<&str>) -> Produced {
+        let records = match partition.records {
+            Ok(records) => records,
+            Err(Invalid::Compression(compression)) => {
+                let message = format!("Unsupported compression type {compression}");
+                return Produced::error(UNSUPPORTED_COMPRESSION_TYPE, message);
+            }
+            Err(Invalid::Corrupt(e)) => return Produced::error(CORRUPT_MESSAGE, e.to_string()),
+            Err(Invalid::NullValue) => {
+                let message = "Records with null values are not supported".to_string();
+                return Produced::error(INVALID_RECORD, message);
+            }
+        };
+        let results: Vec<_> = futures::stream::iter(records)
+            .map(|record| async move {
+                let mut headers: Vec<(&str, &[u8])> = record
+                    .headers
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_slice()))
+                    .collect();
+                if let (Some(header), Some(subject)) = (&self.subject_header, subject) {
+                    headers.push((header.as_str(), subject.as_bytes()));
+                }
+                let message = Message {
+                    topic: Some(topic),
+                    key: record.key.as_deref(),
+                    headers,
+                    payload: &record.value,
+                };
+                self.sink.send_with_ack(message, AckMode::Delivered).await
+            })
+            .buffered(self.concurrency_limit.max(1))
+            .collect()
+            .await;
+        match results.into_iter().collect::<Result<Vec<_>>>() {
+            Ok(_) => Produced {
+                error_code: NONE,
+                error_message: None,
+            },
+            Err(e) => {
+                log::warn!("{e}");
+                Produced::error(UNKNOWN_SERVER_ERROR, e.to_string())
+            }
+        }
+    }
+
+    fn produce(&self, version: i16, reader: &mut Reader, subject: Option<&str>) -> Result<Pending> {
+        let transactional_id = reader.nullable_string()?;
+        let acks = reader.i16()?;
+        let _timeout_ms = reader.i32()?;
+        let mut topics = Vec::new();
+        for _ in 0..reader.array_len()?.unwrap_or_default() {
+            let name = reader.string()?.to_string();
+            let mut partitions = Vec::new();
+            for _ in 0..reader.array_len()?.unwrap_or_default() {
+                let index = reader.i32()?;
+                let batches = reader.nullable_bytes()?.unwrap_or_default();
+                let records = match transactional_id {
+                    Some(_) => Err(Invalid::Corrupt(anyhow::anyhow!(
+                        "Transactions are not supported"
+                    ))),
+                    None => records::decode(batches, self.max_message_size),
+                };
+                partitions.push(Partition { index, records });
+            }
+            topics.push(Topic { name, partitions });
+        }
+
+        let context = self.clone();
+        let subject = subject.map(str::to_string);
+        let send = tokio::spawn(async move {
+            let mut body = Vec::new();
+            body.put_i32(topics.len() as i32);
+            for topic in topics {
+                put_string(&mut body, &topic.name);
+                body.put_i32(topic.partitions.len() as i32);
+                for partition in topic.partitions {
+                    body.put_i32(partition.index);
+                    let produced = context
+                        .send(&topic.name, partition, subject.as_deref())
+                        .await;
+                    body.put_i16(produced.error_code);
+                    // Records are repartitioned by the proxy, so there is no offset to report.
+                    body.put_i64(-1);
+                    body.put_i64(-1);
+                    if version >= 5 {
+                        body.put_i64(-1);
+                    }
+                    if version >= 8 {
+                        body.put_i32(0);
+                        put_nullable_string(&mut body, produced.error_message.as_deref());
+                    }
+                }
+            }
+            body.put_i32(0);
+            body
+        });
+        match acks {
+            0 => Ok(Pending::Unacknowledged(send)),
+            _ => Ok(Pending::Produce(send)),
+        }
+    }
+
+    fn handle(&self, request: &[u8], subject: Option<&str>) -> Result<(i32, Pending)> {
+        let mut reader = Reader::new(request);
+        let api_key = reader.i16()?;
+        let api_version = reader.i16()?;
+        let correlation_id = reader.i32()?;
+        let _client_id = reader.nullable_string()?;
+        if api_key == API_VERSIONS {
+            return Ok((correlation_id, Pending::Ready(api_versions(api_version))));
+        }
+        let supported = APIS
+            .iter()
+            .any(|(key, min, max)| *key == api_key && (*min..=*max).contains(&api_version));
+        if !supported {
+            bail!("Unsupported API {api_key} version {api_version}");
+        }
+        let pending = match api_key {
+            PRODUCE => self.produce(api_version, &mut reader, subject)?,
+            METADATA => Pending::Ready(self.metadata(api_version, &mut reader)?),
+            _ => Pending::Ready(init_producer_id(&mut reader, &self.producer_ids)?),
+        };
+        Ok((correlation_id, pending))
+    }
+}
+
+async fn read_request<R>(reader: &mut R, max_message_size: usize) -> Result<Option<Vec<u8>>>
+where
+    R: AsyncRead + Unpin,
+{
+    let size = match reader.read_i32().await {
+        Ok(size) => usize::try_from(size)?,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if size > max_message_size {
+        bail!("Request exceeds {max_message_size} bytes");
+    }
+    let mut request = vec![0; size];
+    reader.read_exact(&mut request).await?;
+    Ok(Some(request))
+}
+
+async fn connection<S>(
+    stream: S,
+    subject: Option<ClientSubject>,
+    context: Context,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let subject = subject.map(|ClientSubject(subject)| subject);
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (pending_snd, mut pending_rcv) =
+        mpsc::channel::<(i32, Pending)>(context.concurrency_limit.max(1));
+    let responses = tokio::spawn(async move {
+        while let Some((correlation_id, pending)) = pending_rcv.recv().await {
+            let body = match pending {
+                Pending::Ready(body) => body,
+                Pending::Produce(send) => send.await?,
+                Pending::Unacknowledged(send) => {
+                    send.await?;
+                    continue;
+                }
+            };
+            let mut response = Vec::with_capacity(8 + body.len());
+            response.put_i32(4 + body.len() as i32);
+            response.put_i32(correlation_id);
+            response.extend_from_slice(&body);
+            writer.write_all(&response).await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let result = loop {
+        let request = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break Ok(()),
+            request = async {
+                backpressure(&context.sink).await;
+                read_request(&mut reader, context.max_message_size).await
+            } => request,
+        };
+        let request = match request {
+            Ok(Some(request)) => request,
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        };
+        match context.handle(&request, subject.as_deref()) {
+            Ok(pending) => {
+                if pending_snd.send(pending).await.is_err() {
+                    break Ok(());
+                }
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    drop(pending_snd);
+    responses.await??;
+    result
+}
+
+#[async_trait]
+impl Server for KafkaServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let (host, port) = match &self.advertised_address {
+            None => (self.address.ip().to_string(), self.address.port()),
+            Some(address) => {
+                let (host, port) = address.rsplit_once(':').context("Expected HOST:PORT")?;
+                (host.to_string(), port.parse()?)
+            }
+        };
+        let context = Context {
+            sink,
+            concurrency_limit: self.concurrency_limit,
+            max_message_size: self.max_message_size,
+            host,
+            port: port.into(),
+            subject_header: self.tls.tls_subject_header.clone(),
+            producer_ids: Arc::new(AtomicI64::new(0)),
+        };
+        let acceptor = TlsAcceptor::new(&self.tls)?;
+        let listener = TcpListener::bind(&self.address).await?;
+        serve_streams(
+            listener,
+            acceptor,
+            shutdown_trigger_receiver,
+            move |stream, subject, shutdown_trigger_receiver| {
+                connection(stream, subject, context.clone(), shutdown_trigger_receiver)
+            },
+        )
+        .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::protocol::{Reader, put_nullable_string, put_string};
+    use super::records::Record;
+    use super::records::tests::encode;
+    use crate::cli::{KafkaServer, Tls};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry};
+    use bytes::BufMut;
+    use rdkafka::ClientConfig;
+    use rdkafka::message::{Header, OwnedHeaders};
+    use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn request(
+        stream: &mut TcpStream,
+        api_key: i16,
+        api_version: i16,
+        correlation_id: i32,
+        body: &[u8],
+    ) -> Vec<u8> {
+        let mut request = Vec::new();
+        request.put_i16(api_key);
+        request.put_i16(api_version);
+        request.put_i32(correlation_id);
+        put_nullable_string(&mut request, Some("test"));
+        request.extend_from_slice(body);
+        stream.write_i32(request.len() as i32).await.unwrap();
+        stream.write_all(&request).await.unwrap();
+
+        let len = stream.read_i32().await.unwrap();
+        let mut response = vec![0; len as usize];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(response[..4], correlation_id.to_be_bytes());
+        response.split_off(4)
+    }
+
+    #[tokio::test]
+    async fn test_kafka() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(KafkaServer {
+            address,
+            advertised_address: Some("proxy:9092".to_string()),
+            concurrency_limit: 16,
+            max_message_size: 1024 * 1024,
+            tls: Tls::default(),
+        });
+        let mut stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+
+        let response = request(&mut stream, 18, 0, 1, &[]).await;
+        let mut reader = Reader::new(&response);
+        assert_eq!(reader.i16().unwrap(), 0);
+        let apis: Vec<_> = (0..reader.i32().unwrap())
+            .map(|_| {
+                let api = (reader.i16().unwrap(), reader.i16().unwrap());
+                reader.i16().unwrap();
+                api
+            })
+            .collect();
+        assert_eq!(apis, vec![(0, 3), (3, 0), (18, 0), (22, 0)]);
+
+        let mut body = Vec::new();
+        body.put_i32(1);
+        put_string(&mut body, "events");
+        let response = request(&mut stream, 3, 1, 2, &body).await;
+        let mut reader = Reader::new(&response);
+        assert_eq!(reader.i32().unwrap(), 1);
+        assert_eq!(reader.i32().unwrap(), 0);
+        assert_eq!(reader.string().unwrap(), "proxy");
+        assert_eq!(reader.i32().unwrap(), 9092);
+        assert_eq!(reader.nullable_string().unwrap(), None);
+        assert_eq!(reader.i32().unwrap(), 0);
+        assert_eq!(reader.i32().unwrap(), 1);
+        assert_eq!(reader.i16().unwrap(), 0);
+        assert_eq!(reader.string().unwrap(), "events");
+
+        let records = [
+            Record {
+                key: Some(b"key".to_vec()),
+                value: b"first".to_vec(),
+                headers: vec![("header".to_string(), b"value".to_vec())],
+            },
+            Record {
+                key: None,
+                value: b"second".to_vec(),
+                headers: Vec::new(),
+            },
+        ];
+        let batch = encode(&records, 1);
+        let mut body = Vec::new();
+        put_nullable_string(&mut body, None);
+        body.put_i16(-1);
+        body.put_i32(30000);
+        body.put_i32(1);
+        put_string(&mut body, "events");
+        body.put_i32(1);
+        body.put_i32(0);
+        body.put_i32(batch.len() as i32);
+        body.extend_from_slice(&batch);
+        let response = request(&mut stream, 0, 8, 3, &body).await;
+        let mut reader = Reader::new(&response);
+        assert_eq!(reader.i32().unwrap(), 1);
+        assert_eq!(reader.string().unwrap(), "events");
+        assert_eq!(reader.i32().unwrap(), 1);
+        assert_eq!(reader.i32().unwrap(), 0);
+        assert_eq!(reader.i16().unwrap(), 0);
+        assert_eq!(reader.i64().unwrap(), -1);
+
+        let mut messages = server.sink.messages().await;
+        messages.sort();
+        assert_eq!(messages, vec![b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(
+            server.sink.topics().await,
+            vec![Some("events".to_string()); 2]
+        );
+        let mut keys = server.sink.keys().await;
+        keys.sort();
+        assert_eq!(keys, vec![None, Some(b"key".to_vec())]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_kafka_client() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(KafkaServer {
+            address,
+            advertised_address: None,
+            concurrency_limit: 16,
+            max_message_size: 1024 * 1024,
+            tls: Tls::default(),
+        });
+        retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", address.to_string())
+            .set("compression.codec", "gzip")
+            .set("message.timeout.ms", "10000")
+            .create()
+            .unwrap();
+        let metadata = tokio::task::spawn_blocking({
+            let producer = producer.clone();
+            move || {
+                producer
+                    .client()
+                    .fetch_metadata(Some("events"), Duration::from_secs(10))
+                    .unwrap()
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(metadata.orig_broker_id(), 0);
+        assert_eq!(metadata.topics()[0].name(), "events");
+        assert_eq!(metadata.topics()[0].partitions().len(), 1);
+
+        let headers = OwnedHeaders::new().insert(Header {
+            key: "header",
+            value: Some("value"),
+        });
+        producer
+            .send(
+                FutureRecord::to("events")
+                    .key("key")
+                    .payload("payload")
+                    .headers(headers),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(server.sink.messages().await, vec![b"payload".to_vec()]);
+        assert_eq!(server.sink.keys().await, vec![Some(b"key".to_vec())]);
+        assert_eq!(
+            server.sink.headers().await,
+            vec![vec![("header".to_string(), b"value".to_vec())]]
+        );
+
+        drop(producer);
+        server.stop().await;
+    }
+}
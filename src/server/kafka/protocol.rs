@@ -0,0 +1,163 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Result, bail};
+use bytes::BufMut;
+
+pub struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader(buf)
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("Unexpected end of data");
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    pub fn i8(&mut self) -> Result<i8> {
+        Ok(i8::from_be_bytes(self.array()?))
+    }
+
+    pub fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+
+    pub fn unsigned_varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let [byte] = self.array()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("Varint exceeds 64 bits");
+    }
+
+    pub fn varlong(&mut self) -> Result<i64> {
+        let value = self.unsigned_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    pub fn varint(&mut self) -> Result<i32> {
+        Ok(i32::try_from(self.varlong()?)?)
+    }
+
+    fn sized(&mut self, len: i64) -> Result<Option<&'a [u8]>> {
+        match len {
+            -1 => Ok(None),
+            len => Ok(Some(self.take(usize::try_from(len)?)?)),
+        }
+    }
+
+    pub fn nullable_string(&mut self) -> Result<Option<&'a str>> {
+        let len = self.i16()?.into();
+        match self.sized(len)? {
+            None => Ok(None),
+            Some(s) => Ok(Some(std::str::from_utf8(s)?)),
+        }
+    }
+
+    pub fn string(&mut self) -> Result<&'a str> {
+        match self.nullable_string()? {
+            None => bail!("Unexpected null string"),
+            Some(s) => Ok(s),
+        }
+    }
+
+    pub fn nullable_bytes(&mut self) -> Result<Option<&'a [u8]>> {
+        let len = self.i32()?.into();
+        self.sized(len)
+    }
+
+    pub fn varint_bytes(&mut self) -> Result<Option<&'a [u8]>> {
+        let len = self.varlong()?;
+        self.sized(len)
+    }
+
+    pub fn array_len(&mut self) -> Result<Option<usize>> {
+        match self.i32()? {
+            -1 => Ok(None),
+            len => Ok(Some(usize::try_from(len)?)),
+        }
+    }
+}
+
+pub fn put_nullable_string(buf: &mut Vec<u8>, s: Option<&str>) {
+    match s {
+        None => buf.put_i16(-1),
+        Some(s) => {
+            buf.put_i16(s.len() as i16);
+            buf.put_slice(s.as_bytes());
+        }
+    }
+}
+
+pub fn put_string(buf: &mut Vec<u8>, s: &str) {
+    put_nullable_string(buf, Some(s));
+}
+
+pub fn put_unsigned_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reader, put_unsigned_varint};
+
+    #[test]
+    fn test_varint() {
+        let mut buf = Vec::new();
+        for value in [0, 1, 2, 3, 300, u32::MAX as u64] {
+            put_unsigned_varint(&mut buf, value);
+        }
+        let mut reader = Reader::new(&buf);
+        assert_eq!(reader.varlong().unwrap(), 0);
+        assert_eq!(reader.varlong().unwrap(), -1);
+        assert_eq!(reader.varlong().unwrap(), 1);
+        assert_eq!(reader.varlong().unwrap(), -2);
+        assert_eq!(reader.unsigned_varint().unwrap(), 300);
+        assert_eq!(reader.varint().unwrap(), i32::MIN);
+        assert!(reader.remaining().is_empty());
+        assert!(reader.i8().is_err());
+    }
+}
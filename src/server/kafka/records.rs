@@ -0,0 +1,272 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Read;
+
+use anyhow::{Context, Result, anyhow};
+use flate2::read::MultiGzDecoder;
+
+use crate::server::kafka::protocol::Reader;
+
+const MAGIC: i8 = 2;
+const COMPRESSION_MASK: i16 = 0x07;
+const CONTROL_FLAG: i16 = 0x20;
+const CRC32C: [u32; 256] = crc32c_table();
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub key: Option<Vec<u8>>,
+    pub value: Vec<u8>,
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+pub enum Invalid {
+    Corrupt(anyhow::Error),
+    Compression(i16),
+    NullValue,
+}
+
+impl From<anyhow::Error> for Invalid {
+    fn from(e: anyhow::Error) -> Invalid {
+        Invalid::Corrupt(e)
+    }
+}
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0x82f63b78
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, b| {
+        CRC32C[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn record(reader: &mut Reader) -> Result<Record, Invalid> {
+    let len = reader.varint()?;
+    let len = usize::try_from(len).map_err(anyhow::Error::from)?;
+    let mut reader = Reader::new(reader.take(len)?);
+    let _attributes = reader.i8()?;
+    let _timestamp_delta = reader.varlong()?;
+    let _offset_delta = reader.varint()?;
+    let key = reader.varint_bytes()?.map(<[u8]>::to_vec);
+    let value = reader.varint_bytes()?.ok_or(Invalid::NullValue)?.to_vec();
+    let headers = (0..reader.varint()?)
+        .map(|_| {
+            let key = reader
+                .varint_bytes()?
+                .context("Unexpected null header key")?;
+            let value = reader.varint_bytes()?.unwrap_or_default();
+            Ok((String::from_utf8(key.to_vec())?, value.to_vec()))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Record {
+        key,
+        value,
+        headers,
+    })
+}
+
+fn batch(batch: &[u8], max_message_size: usize, records: &mut Vec<Record>) -> Result<(), Invalid> {
+    let mut reader = Reader::new(batch);
+    let _partition_leader_epoch = reader.i32()?;
+    let magic = reader.i8()?;
+    if magic != MAGIC {
+        return Err(anyhow!("Unsupported record batch magic {magic}").into());
+    }
+    let crc = reader.i32()? as u32;
+    if crc32c(reader.remaining()) != crc {
+        return Err(anyhow!("Record batch CRC mismatch").into());
+    }
+    let attributes = reader.i16()?;
+    // lastOffsetDelta, baseTimestamp, maxTimestamp, producerId, producerEpoch and baseSequence
+    reader.take(4 + 8 + 8 + 8 + 2 + 4)?;
+    let count = reader.i32()?;
+    if attributes & CONTROL_FLAG != 0 {
+        return Ok(());
+    }
+    let decompressed;
+    let mut reader = match attributes & COMPRESSION_MASK {
+        0 => reader,
+        1 => {
+            let mut buf = Vec::new();
+            MultiGzDecoder::new(reader.remaining())
+                .take(max_message_size as u64 + 1)
+                .read_to_end(&mut buf)
+                .map_err(anyhow::Error::from)?;
+            if buf.len() > max_message_size {
+                return Err(anyhow!("Decompressed records exceed {max_message_size} bytes").into());
+            }
+            decompressed = buf;
+            Reader::new(&decompressed)
+        }
+        compression => return Err(Invalid::Compression(compression)),
+    };
+    for _ in 0..count {
+        records.push(record(&mut reader)?);
+    }
+    Ok(())
+}
+
+pub fn decode(records: &[u8], max_message_size: usize) -> Result<Vec<Record>, Invalid> {
+    let mut reader = Reader::new(records);
+    let mut decoded = Vec::new();
+    while !reader.remaining().is_empty() {
+        let _base_offset = reader.i64()?;
+        let len = reader.i32()?;
+        let len = usize::try_from(len).map_err(anyhow::Error::from)?;
+        batch(reader.take(len)?, max_message_size, &mut decoded)?;
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Invalid, Record, crc32c, decode, record};
+    use crate::server::kafka::protocol::{Reader, put_unsigned_varint};
+    use bytes::BufMut;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn put_varint(buf: &mut Vec<u8>, value: i64) {
+        put_unsigned_varint(buf, ((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn put_varint_bytes(buf: &mut Vec<u8>, bytes: Option<&[u8]>) {
+        match bytes {
+            None => put_varint(buf, -1),
+            Some(bytes) => {
+                put_varint(buf, bytes.len() as i64);
+                buf.put_slice(bytes);
+            }
+        }
+    }
+
+    pub fn encode(records: &[Record], compression: i16) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        for (offset_delta, record) in records.iter().enumerate() {
+            let mut buf = Vec::new();
+            buf.put_i8(0);
+            put_varint(&mut buf, 0);
+            put_varint(&mut buf, offset_delta as i64);
+            put_varint_bytes(&mut buf, record.key.as_deref());
+            put_varint_bytes(&mut buf, Some(&record.value));
+            put_varint(&mut buf, record.headers.len() as i64);
+            for (key, value) in &record.headers {
+                put_varint_bytes(&mut buf, Some(key.as_bytes()));
+                put_varint_bytes(&mut buf, Some(value));
+            }
+            put_varint(&mut encoded, buf.len() as i64);
+            encoded.extend_from_slice(&buf);
+        }
+        if compression == 1 {
+            let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+            gzip.write_all(&encoded).unwrap();
+            encoded = gzip.finish().unwrap();
+        }
+
+        let mut body = Vec::new();
+        body.put_i16(compression);
+        body.put_i32(records.len() as i32 - 1);
+        body.put_i64(0);
+        body.put_i64(0);
+        body.put_i64(-1);
+        body.put_i16(-1);
+        body.put_i32(-1);
+        body.put_i32(records.len() as i32);
+        body.extend_from_slice(&encoded);
+
+        let mut batch = Vec::new();
+        batch.put_i64(0);
+        batch.put_i32(4 + 1 + 4 + body.len() as i32);
+        batch.put_i32(-1);
+        batch.put_i8(2);
+        batch.put_u32(crc32c(&body));
+        batch.extend_from_slice(&body);
+        batch
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+    }
+
+    #[test]
+    fn test_decode() {
+        let records = vec![
+            Record {
+                key: Some(b"key".to_vec()),
+                value: b"first".to_vec(),
+                headers: vec![("header".to_string(), b"value".to_vec())],
+            },
+            Record {
+                key: None,
+                value: b"second".to_vec(),
+                headers: Vec::new(),
+            },
+        ];
+        let mut batches = encode(&records, 0);
+        batches.extend_from_slice(&encode(&records[..1], 1));
+        let decoded = decode(&batches, 1024).unwrap();
+        assert_eq!(decoded[..2], records);
+        assert_eq!(decoded[2], records[0]);
+        assert_eq!(decoded.len(), 3);
+
+        assert!(matches!(
+            decode(&encode(&records, 4), 1024),
+            Err(Invalid::Compression(4))
+        ));
+        let mut corrupt = encode(&records, 0);
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(decode(&corrupt, 1024), Err(Invalid::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_null_value() {
+        let mut buf = Vec::new();
+        buf.put_i8(0);
+        put_varint(&mut buf, 0);
+        put_varint(&mut buf, 0);
+        put_varint_bytes(&mut buf, Some(b"key"));
+        put_varint_bytes(&mut buf, None);
+        put_varint(&mut buf, 0);
+        let mut encoded = Vec::new();
+        put_varint(&mut encoded, buf.len() as i64);
+        encoded.extend_from_slice(&buf);
+        assert!(matches!(
+            record(&mut Reader::new(&encoded)),
+            Err(Invalid::NullValue)
+        ));
+    }
+}
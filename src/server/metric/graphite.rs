@@ -0,0 +1,139 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc::Sender;
+
+use crate::cli::GraphiteServer;
+use crate::server::metric::{Listener, Metric, Sample, number, serve};
+use crate::server::{Server, SharedSink};
+
+fn parse(line: &str) -> Result<Vec<Metric>> {
+    let mut fields = line.split_whitespace();
+    let (Some(path), Some(value)) = (fields.next(), fields.next()) else {
+        bail!("Expected \"path value timestamp\"");
+    };
+    let timestamp = match fields.next() {
+        None => None,
+        Some(timestamp) => Some(number(timestamp)? as i64).filter(|timestamp| *timestamp >= 0),
+    };
+    let mut path = path.split(';');
+    let name = path.next().context("Missing path")?;
+    let tags = path
+        .map(|tag| {
+            let (key, value) = tag.split_once('=').context("Expected \"tag=value\"")?;
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect::<Result<BTreeMap<_, _>>>()?;
+    Ok(vec![Metric {
+        name: name.to_string(),
+        kind: None,
+        sample: Sample::Value(number(value)?),
+        sample_rate: 1.0,
+        tags,
+        timestamp,
+    }])
+}
+
+#[async_trait]
+impl Server for GraphiteServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let listener = Listener {
+            address: self.address,
+            transport: self.transport,
+            concurrency_limit: self.concurrency_limit,
+            max_message_size: self.max_message_size,
+            flush_interval: self.flush_interval,
+        };
+        serve(listener, sink, shutdown_trigger_receiver, parse).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::cli::{GraphiteServer, Transport};
+    use crate::server::metric::Sample;
+    use crate::server::tests::{RunningServer, free_tcp_address, retry};
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_parse() {
+        let metrics = parse("servers.web01.cpu;dc=eu;role=web 42.5 1700000000").unwrap();
+        assert_eq!(metrics[0].name, "servers.web01.cpu");
+        assert_eq!(metrics[0].sample, Sample::Value(42.5));
+        assert_eq!(metrics[0].timestamp, Some(1700000000));
+        assert_eq!(
+            metrics[0].tags,
+            BTreeMap::from([
+                ("dc".to_string(), "eu".to_string()),
+                ("role".to_string(), "web".to_string()),
+            ])
+        );
+        assert_eq!(parse("metric 1 -1").unwrap()[0].timestamp, None);
+        assert!(parse("metric").is_err());
+        assert!(parse("metric;tag 1 1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_graphite() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(GraphiteServer {
+            address,
+            transport: Transport::Tcp,
+            concurrency_limit: 16,
+            max_message_size: 1024,
+            flush_interval: Some(3600),
+        });
+
+        let mut stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+        stream
+            .write_all(b"load 1 1700000000\nload 3 1700000010\n")
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(server.sink.messages().await.is_empty());
+
+        let sink = server.sink.clone();
+        server.stop().await;
+        let messages = sink.messages().await;
+        assert_eq!(messages.len(), 1);
+        let message: Value = serde_json::from_slice(&messages[0]).unwrap();
+        assert_eq!(
+            message,
+            json!({
+                "name": "load",
+                "tags": {},
+                "timestamp": 1700000010,
+                "count": 2.0,
+                "value": 3.0,
+            })
+        );
+    }
+}
@@ -0,0 +1,482 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod graphite;
+mod statsd;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use futures::StreamExt;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Sender;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::cli::Transport;
+use crate::server::{Message, SharedSink, backpressure};
+
+type Parser = fn(&str) -> Result<Vec<Metric>>;
+
+type Series = (String, Option<Kind>, Vec<(String, String)>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Counter,
+    Gauge,
+    Timer,
+    Histogram,
+    Distribution,
+    Set,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Sample {
+    Value(f64),
+    Delta(f64),
+    Member(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Metric {
+    name: String,
+    kind: Option<Kind>,
+    sample: Sample,
+    sample_rate: f64,
+    tags: BTreeMap<String, String>,
+    timestamp: Option<i64>,
+}
+
+struct Aggregate {
+    count: f64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    last: f64,
+    members: HashSet<String>,
+    timestamp: Option<i64>,
+}
+
+#[derive(Default)]
+struct Aggregator {
+    series: Mutex<HashMap<Series, Aggregate>>,
+}
+
+struct Listener {
+    address: SocketAddr,
+    transport: Transport,
+    concurrency_limit: usize,
+    max_message_size: usize,
+    flush_interval: Option<u64>,
+}
+
+#[derive(Clone)]
+struct Context {
+    sink: SharedSink,
+    parse: Parser,
+    records: Sender<(String, Value)>,
+    aggregator: Option<Arc<Aggregator>>,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Timer => "timer",
+            Kind::Histogram => "histogram",
+            Kind::Distribution => "distribution",
+            Kind::Set => "set",
+        }
+    }
+}
+
+fn number(value: &str) -> Result<f64> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => bail!("Invalid number {value:?}"),
+    }
+}
+
+impl Metric {
+    fn record(&self) -> Value {
+        let value = match &self.sample {
+            Sample::Value(value) | Sample::Delta(value) => json!(value),
+            Sample::Member(member) => json!(member),
+        };
+        let mut record = json!({
+            "name": self.name,
+            "value": value,
+            "tags": self.tags,
+            "timestamp": self.timestamp,
+        });
+        if let Some(kind) = self.kind {
+            record["type"] = kind.name().into();
+            record["sample_rate"] = self.sample_rate.into();
+            record["delta"] = matches!(self.sample, Sample::Delta(_)).into();
+        }
+        record
+    }
+}
+
+impl Aggregate {
+    fn record(&self, name: &str, kind: Option<Kind>, tags: &[(String, String)], now: i64) -> Value {
+        let tags: BTreeMap<&str, &str> = tags
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let value = match kind {
+            Some(Kind::Counter) => json!(self.sum),
+            Some(Kind::Set) => json!(self.members.len()),
+            Some(Kind::Timer | Kind::Histogram | Kind::Distribution) => {
+                json!(self.sum / self.count)
+            }
+            Some(Kind::Gauge) | None => json!(self.last),
+        };
+        let mut record = json!({
+            "name": name,
+            "value": value,
+            "tags": tags,
+            "timestamp": self.timestamp.unwrap_or(now),
+            "count": self.count,
+        });
+        if let Some(Kind::Timer | Kind::Histogram | Kind::Distribution) = kind {
+            record["min"] = self.min.into();
+            record["max"] = self.max.into();
+            record["sum"] = self.sum.into();
+        }
+        if let Some(kind) = kind {
+            record["type"] = kind.name().into();
+        }
+        record
+    }
+}
+
+impl Aggregator {
+    fn add(&self, metric: Metric) {
+        let key = (metric.name, metric.kind, metric.tags.into_iter().collect());
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let aggregate = series.entry(key).or_insert_with(|| Aggregate {
+            count: 0.0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            last: 0.0,
+            members: HashSet::new(),
+            timestamp: None,
+        });
+        let weight = 1.0 / metric.sample_rate;
+        aggregate.count += weight;
+        aggregate.timestamp = metric.timestamp.or(aggregate.timestamp);
+        match metric.sample {
+            Sample::Value(value) => {
+                aggregate.sum += value * weight;
+                aggregate.min = aggregate.min.min(value);
+                aggregate.max = aggregate.max.max(value);
+                aggregate.last = value;
+            }
+            Sample::Delta(delta) => aggregate.last += delta,
+            Sample::Member(member) => {
+                aggregate.members.insert(member);
+            }
+        }
+    }
+
+    fn flush(&self) -> Vec<(String, Value)> {
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let now = chrono::Utc::now().timestamp();
+        let mut records = Vec::new();
+        // Gauges keep their last value, so deltas after a flush are applied to it,
+        // but are forgotten once they go a whole interval without updates
+        series.retain(|(name, kind, tags), aggregate| {
+            let updated = aggregate.count > 0.0;
+            if updated {
+                records.push((name.clone(), aggregate.record(name, *kind, tags, now)));
+            }
+            aggregate.count = 0.0;
+            aggregate.timestamp = None;
+            updated && *kind == Some(Kind::Gauge)
+        });
+        records
+    }
+}
+
+impl Context {
+    async fn handle(&self, lines: &str) {
+        for line in lines.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let metrics = match (self.parse)(line) {
+                Ok(metrics) => metrics,
+                Err(e) => {
+                    log::warn!("Failed to parse {line:?}: {e}");
+                    continue;
+                }
+            };
+            for metric in metrics {
+                match &self.aggregator {
+                    Some(aggregator) => aggregator.add(metric),
+                    None => {
+                        let record = metric.record();
+                        if self.records.send((metric.name, record)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn flush(
+    aggregator: Arc<Aggregator>,
+    records: Sender<(String, Value)>,
+    flush_interval: u64,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(flush_interval));
+    interval.tick().await;
+    loop {
+        let stop = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => true,
+            _ = interval.tick() => false,
+        };
+        for record in aggregator.flush() {
+            if records.send(record).await.is_err() {
+                return;
+            }
+        }
+        if stop {
+            break;
+        }
+    }
+}
+
+async fn udp(
+    socket: UdpSocket,
+    context: Context,
+    max_message_size: usize,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) {
+    let mut buf = vec![0; max_message_size];
+    loop {
+        let received = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            received = socket.recv(&mut buf) => received,
+        };
+        match received {
+            Ok(_) if context.sink.saturated() => context.sink.rejected("backpressure"),
+            Ok(len) => context.handle(&String::from_utf8_lossy(&buf[..len])).await,
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+}
+
+async fn tcp(
+    listener: TcpListener,
+    context: Context,
+    max_message_size: usize,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) {
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    log::warn!("Failed to accept connection: {e}");
+                    continue;
+                }
+            },
+        };
+        let context = context.clone();
+        let mut shutdown_trigger_receiver = shutdown_trigger_receiver.resubscribe();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stream);
+            let mut line = Vec::new();
+            loop {
+                let read = tokio::select! {
+                    _ = shutdown_trigger_receiver.recv() => break,
+                    read = async {
+                        backpressure(&context.sink).await;
+                        line.clear();
+                        (&mut reader)
+                            .take(max_message_size as u64)
+                            .read_until(b'\n', &mut line)
+                            .await
+                    } => read,
+                };
+                match read {
+                    Ok(0) => break,
+                    Ok(_) if line.len() >= max_message_size && !line.ends_with(b"\n") => {
+                        log::warn!("Line exceeds {max_message_size} bytes");
+                        break;
+                    }
+                    Ok(_) => context.handle(&String::from_utf8_lossy(&line)).await,
+                    Err(e) => {
+                        log::warn!("{e}");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn serve(
+    listener: Listener,
+    sink: SharedSink,
+    shutdown_trigger_receiver: Receiver<()>,
+    parse: Parser,
+) -> Result<()> {
+    let (records, rcv) = mpsc::channel(1);
+    let aggregator = match listener.flush_interval {
+        None => None,
+        Some(flush_interval) => {
+            let aggregator = Arc::new(Aggregator::default());
+            tokio::spawn(flush(
+                aggregator.clone(),
+                records.clone(),
+                flush_interval,
+                shutdown_trigger_receiver.resubscribe(),
+            ));
+            Some(aggregator)
+        }
+    };
+    let context = Context {
+        sink: sink.clone(),
+        parse,
+        records,
+        aggregator,
+    };
+    match listener.transport {
+        Transport::Udp => {
+            let socket = UdpSocket::bind(listener.address).await?;
+            tokio::spawn(udp(
+                socket,
+                context,
+                listener.max_message_size,
+                shutdown_trigger_receiver,
+            ));
+        }
+        Transport::Tcp => {
+            let tcp_listener = TcpListener::bind(listener.address).await?;
+            tokio::spawn(tcp(
+                tcp_listener,
+                context,
+                listener.max_message_size,
+                shutdown_trigger_receiver,
+            ));
+        }
+    }
+
+    let sink = &sink;
+    ReceiverStream::new(rcv)
+        .for_each_concurrent(listener.concurrency_limit, |(name, record)| async move {
+            let payload = match serde_json::to_vec(&record) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    log::warn!("{e}");
+                    return;
+                }
+            };
+            let message = Message {
+                topic: None,
+                key: Some(name.as_bytes()),
+                headers: Vec::new(),
+                payload: &payload,
+            };
+            if let Err(e) = sink.send_message(message).await {
+                log::warn!("{e}");
+            }
+        })
+        .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregator, Kind, Metric, Sample};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn metric(kind: Kind, sample: Sample, sample_rate: f64) -> Metric {
+        Metric {
+            name: "metric".to_string(),
+            kind: Some(kind),
+            sample,
+            sample_rate,
+            tags: BTreeMap::new(),
+            timestamp: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_aggregator() {
+        let aggregator = Aggregator::default();
+        aggregator.add(metric(Kind::Counter, Sample::Value(1.0), 0.5));
+        aggregator.add(metric(Kind::Counter, Sample::Value(3.0), 1.0));
+        aggregator.add(metric(Kind::Timer, Sample::Value(10.0), 1.0));
+        aggregator.add(metric(Kind::Timer, Sample::Value(30.0), 1.0));
+        aggregator.add(metric(Kind::Gauge, Sample::Value(5.0), 1.0));
+        aggregator.add(metric(Kind::Gauge, Sample::Delta(-2.0), 1.0));
+        aggregator.add(metric(Kind::Set, Sample::Member("a".to_string()), 1.0));
+        aggregator.add(metric(Kind::Set, Sample::Member("a".to_string()), 1.0));
+
+        let mut records: Vec<_> = aggregator
+            .flush()
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect();
+        records.sort_by_key(|record| record["type"].to_string());
+        let expected = [
+            json!({"type": "counter", "value": 5.0, "count": 3.0}),
+            json!({"type": "gauge", "value": 3.0, "count": 2.0}),
+            json!({"type": "set", "value": 1, "count": 2.0}),
+            json!({"type": "timer", "value": 20.0, "count": 2.0, "min": 10.0, "max": 30.0, "sum": 40.0}),
+        ];
+        for (record, expected) in records.iter().zip(expected) {
+            for (key, value) in expected.as_object().unwrap() {
+                assert_eq!(&record[key], value, "{key} of {record}");
+            }
+            assert_eq!(record["name"], "metric");
+            assert_eq!(record["timestamp"], 1);
+        }
+        assert_eq!(records.len(), 4);
+
+        aggregator.add(metric(Kind::Gauge, Sample::Delta(1.0), 1.0));
+        aggregator.add(metric(Kind::Counter, Sample::Value(1.0), 1.0));
+        let mut records: Vec<_> = aggregator
+            .flush()
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect();
+        records.sort_by_key(|record| record["type"].to_string());
+        assert_eq!(records[0]["value"], 1.0);
+        assert_eq!(records[1]["value"], 4.0);
+        assert_eq!(records.len(), 2);
+
+        assert!(aggregator.flush().is_empty());
+        assert!(aggregator.series.lock().unwrap().is_empty());
+        aggregator.add(metric(Kind::Gauge, Sample::Delta(1.0), 1.0));
+        let records = aggregator.flush();
+        assert_eq!(records[0].1["value"], 1.0);
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc::Sender;
+
+use crate::cli::StatsdServer;
+use crate::server::metric::{Kind, Listener, Metric, Sample, number, serve};
+use crate::server::{Server, SharedSink};
+
+fn parse(line: &str) -> Result<Vec<Metric>> {
+    let mut sections = line.split('|');
+    let (name, values) = sections
+        .next()
+        .and_then(|section| section.split_once(':'))
+        .context("Expected \"name:value|type\"")?;
+    let kind = match sections.next().context("Missing metric type")? {
+        "c" => Kind::Counter,
+        "g" => Kind::Gauge,
+        "ms" => Kind::Timer,
+        "h" => Kind::Histogram,
+        "d" => Kind::Distribution,
+        "s" => Kind::Set,
+        kind => bail!("Unknown metric type {kind:?}"),
+    };
+    let mut sample_rate = 1.0;
+    let mut tags = BTreeMap::new();
+    let mut timestamp = None;
+    for section in sections {
+        if let Some(rate) = section.strip_prefix('@') {
+            sample_rate = number(rate)?;
+            if sample_rate <= 0.0 || sample_rate > 1.0 {
+                bail!("Invalid sample rate {rate:?}");
+            }
+        } else if let Some(section) = section.strip_prefix('#') {
+            for tag in section.split(',').filter(|tag| !tag.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                tags.insert(key.to_string(), value.to_string());
+            }
+        } else if let Some(seconds) = section.strip_prefix('T') {
+            timestamp = Some(seconds.parse()?);
+        }
+    }
+    values
+        .split(':')
+        .map(|value| {
+            let sample = match kind {
+                Kind::Set => Sample::Member(value.to_string()),
+                Kind::Gauge if value.starts_with(['+', '-']) => Sample::Delta(number(value)?),
+                _ => Sample::Value(number(value)?),
+            };
+            Ok(Metric {
+                name: name.to_string(),
+                kind: Some(kind),
+                sample,
+                sample_rate,
+                tags: tags.clone(),
+                timestamp,
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Server for StatsdServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let listener = Listener {
+            address: self.address,
+            transport: self.transport,
+            concurrency_limit: self.concurrency_limit,
+            max_message_size: self.max_message_size,
+            flush_interval: self.flush_interval,
+        };
+        serve(listener, sink, shutdown_trigger_receiver, parse).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::cli::{StatsdServer, Transport};
+    use crate::server::metric::{Kind, Sample};
+    use crate::server::tests::{RunningServer, free_udp_address};
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+    use tokio::net::UdpSocket;
+
+    #[test]
+    fn test_parse() {
+        let metrics = parse("page.views:1|c|@0.5|#env:prod,canary").unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].name, "page.views");
+        assert_eq!(metrics[0].kind, Some(Kind::Counter));
+        assert_eq!(metrics[0].sample, Sample::Value(1.0));
+        assert_eq!(metrics[0].sample_rate, 0.5);
+        assert_eq!(
+            metrics[0].tags,
+            BTreeMap::from([
+                ("env".to_string(), "prod".to_string()),
+                ("canary".to_string(), String::new()),
+            ])
+        );
+
+        let metrics = parse("latency:10:20.5|ms|T1700000000").unwrap();
+        assert_eq!(
+            metrics
+                .iter()
+                .map(|metric| metric.sample.clone())
+                .collect::<Vec<_>>(),
+            vec![Sample::Value(10.0), Sample::Value(20.5)]
+        );
+        assert_eq!(metrics[1].timestamp, Some(1700000000));
+        assert_eq!(parse("queue:-3|g").unwrap()[0].sample, Sample::Delta(-3.0));
+        assert_eq!(
+            parse("users:alice|s").unwrap()[0].sample,
+            Sample::Member("alice".to_string())
+        );
+        assert!(parse("invalid").is_err());
+        assert!(parse("invalid:1|x").is_err());
+        assert!(parse("invalid:x|c").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_statsd() {
+        let address = free_udp_address();
+        let server = RunningServer::start(StatsdServer {
+            address,
+            transport: Transport::Udp,
+            concurrency_limit: 16,
+            max_message_size: 1024,
+            flush_interval: None,
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let send = async {
+            loop {
+                socket
+                    .send_to(b"requests:1|c|#method:get\nlatency:12|ms", address)
+                    .await
+                    .unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        };
+        let messages = tokio::select! {
+            _ = send => unreachable!(),
+            messages = server.sink.wait_for(2) => messages,
+        };
+        let messages: Vec<Value> = messages[..2]
+            .iter()
+            .map(|message| serde_json::from_slice(message).unwrap())
+            .collect();
+        assert_eq!(
+            messages[0],
+            json!({
+                "name": "requests",
+                "type": "counter",
+                "value": 1.0,
+                "sample_rate": 1.0,
+                "delta": false,
+                "tags": {"method": "get"},
+                "timestamp": null,
+            })
+        );
+        assert_eq!(messages[1]["name"], "latency");
+        assert_eq!(messages[1]["type"], "timer");
+        assert_eq!(
+            server.sink.keys().await[..2],
+            [Some(b"requests".to_vec()), Some(b"latency".to_vec())]
+        );
+
+        server.stop().await;
+    }
+}
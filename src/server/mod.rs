@@ -14,25 +14,315 @@
  * limitations under the License.
  */
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::mpsc::Sender;
 
-use crate::kafka::KafkaProducer;
+use crate::cli::AckMode;
 
+pub mod auth;
 #[cfg(feature = "coap")]
 mod coap;
 mod decoder;
+#[cfg(feature = "forward")]
+mod forward;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod metric;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod rate_limit;
+mod resp;
 mod rest;
 mod stream;
+mod syslog;
+
+const BACKPRESSURE_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct Message<'a> {
+    pub topic: Option<&'a str>,
+    pub key: Option<&'a [u8]>,
+    pub headers: Vec<(&'a str, &'a [u8])>,
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offset {
+    pub partition: i32,
+    pub offset: i64,
+}
+
+impl<'a> From<&'a [u8]> for Message<'a> {
+    fn from(payload: &'a [u8]) -> Message<'a> {
+        Message {
+            topic: None,
+            key: None,
+            headers: Vec::new(),
+            payload,
+        }
+    }
+}
+
+#[async_trait]
+pub trait MessageSink {
+    fn topic(&self) -> String;
+
+    fn allowed(&self, _topic: &str) -> bool {
+        true
+    }
+
+    fn rejected(&self, _reason: &'static str) {}
+
+    fn saturated(&self) -> bool {
+        false
+    }
+
+    async fn dead_letter(&self, _payload: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    async fn send_message(&self, message: Message<'_>) -> Result<()>;
+
+    async fn send_with_ack(
+        &self,
+        message: Message<'_>,
+        _ack_mode: AckMode,
+    ) -> Result<Option<Offset>> {
+        self.send_message(message).await?;
+        Ok(None)
+    }
+}
+
+pub type SharedSink = Arc<dyn MessageSink + Send + Sync>;
+
+fn reply(result: &Result<Option<Offset>>) -> String {
+    match result {
+        Ok(Some(offset)) => format!("OK {}:{}", offset.partition, offset.offset),
+        Ok(None) => "OK".to_string(),
+        Err(e) => format!("ERR {}", e.to_string().replace(['\r', '\n'], " ")),
+    }
+}
+
+async fn backpressure(sink: &SharedSink) {
+    while sink.saturated() {
+        tokio::time::sleep(BACKPRESSURE_INTERVAL).await;
+    }
+}
 
 #[async_trait]
 pub trait Server {
     async fn run(
         &self,
-        kafka_producer: KafkaProducer,
+        sink: SharedSink,
         shutdown_trigger_receiver: Receiver<()>,
         _shutdown_sender: Sender<()>,
     ) -> Result<()>;
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use anyhow::{Result, anyhow};
+    use async_trait::async_trait;
+    use tokio::sync::{Mutex, Notify, broadcast};
+    use tokio::task::JoinHandle;
+
+    use crate::cli::AckMode;
+    use crate::server::{Message, MessageSink, Offset, Server, reply};
+
+    pub type Headers = Vec<(String, Vec<u8>)>;
+
+    #[derive(Default)]
+    pub struct RecordingSink {
+        messages: Mutex<Vec<Vec<u8>>>,
+        headers: Mutex<Vec<Headers>>,
+        topics: Mutex<Vec<Option<String>>>,
+        keys: Mutex<Vec<Option<Vec<u8>>>>,
+        rejected: std::sync::Mutex<Vec<&'static str>>,
+        saturated: AtomicBool,
+        notify: Notify,
+    }
+
+    impl RecordingSink {
+        pub async fn messages(&self) -> Vec<Vec<u8>> {
+            self.messages.lock().await.clone()
+        }
+
+        pub async fn headers(&self) -> Vec<Headers> {
+            self.headers.lock().await.clone()
+        }
+
+        pub async fn topics(&self) -> Vec<Option<String>> {
+            self.topics.lock().await.clone()
+        }
+
+        pub async fn keys(&self) -> Vec<Option<Vec<u8>>> {
+            self.keys.lock().await.clone()
+        }
+
+        pub fn rejected_reasons(&self) -> Vec<&'static str> {
+            self.rejected.lock().unwrap().clone()
+        }
+
+        pub fn set_saturated(&self, saturated: bool) {
+            self.saturated.store(saturated, Ordering::SeqCst);
+        }
+
+        pub async fn wait_for(&self, count: usize) -> Vec<Vec<u8>> {
+            let wait = async {
+                loop {
+                    let notified = self.notify.notified();
+                    let messages = self.messages().await;
+                    if messages.len() >= count {
+                        return messages;
+                    }
+                    notified.await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), wait)
+                .await
+                .expect("Timed out waiting for messages")
+        }
+    }
+
+    #[async_trait]
+    impl MessageSink for RecordingSink {
+        fn topic(&self) -> String {
+            "topic".to_string()
+        }
+
+        fn rejected(&self, reason: &'static str) {
+            self.rejected.lock().unwrap().push(reason);
+        }
+
+        fn saturated(&self) -> bool {
+            self.saturated.load(Ordering::SeqCst)
+        }
+
+        async fn send_message(&self, message: Message<'_>) -> Result<()> {
+            let headers = message
+                .headers
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_vec()))
+                .collect();
+            self.headers.lock().await.push(headers);
+            self.topics
+                .lock()
+                .await
+                .push(message.topic.map(str::to_string));
+            self.keys.lock().await.push(message.key.map(<[u8]>::to_vec));
+            self.messages.lock().await.push(message.payload.to_vec());
+            self.notify.notify_waiters();
+            Ok(())
+        }
+
+        async fn send_with_ack(
+            &self,
+            message: Message<'_>,
+            _ack_mode: AckMode,
+        ) -> Result<Option<Offset>> {
+            self.send_message(message).await?;
+            Ok(Some(Offset {
+                partition: 0,
+                offset: self.messages().await.len() as i64 - 1,
+            }))
+        }
+    }
+
+    pub struct RunningServer {
+        pub sink: Arc<RecordingSink>,
+        shutdown_trigger: broadcast::Sender<()>,
+        handle: JoinHandle<Result<()>>,
+    }
+
+    impl RunningServer {
+        pub fn start<S>(server: S) -> RunningServer
+        where
+            S: Server + Send + Sync + 'static,
+        {
+            let sink = Arc::new(RecordingSink::default());
+            let (shutdown_trigger, shutdown_trigger_receiver) = broadcast::channel(1);
+            let (shutdown_sender, _) = tokio::sync::mpsc::channel(1);
+            let handle = tokio::spawn({
+                let sink = sink.clone();
+                async move {
+                    server
+                        .run(sink, shutdown_trigger_receiver, shutdown_sender)
+                        .await
+                }
+            });
+            RunningServer {
+                sink,
+                shutdown_trigger,
+                handle,
+            }
+        }
+
+        pub async fn stop(self) {
+            let _ = self.shutdown_trigger.send(());
+            tokio::time::timeout(Duration::from_secs(5), self.handle)
+                .await
+                .expect("Timed out waiting for shutdown")
+                .unwrap()
+                .unwrap();
+        }
+    }
+
+    pub async fn retry<T, F, Fut>(mut f: F) -> T
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        for _ in 0..100 {
+            if let Ok(result) = f().await {
+                return result;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("Server didn't become available");
+    }
+
+    pub fn free_tcp_address() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    pub fn free_udp_address() -> SocketAddr {
+        std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    pub fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("kafka-proxy-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_reply() {
+        let offset = Offset {
+            partition: 2,
+            offset: 42,
+        };
+        assert_eq!(reply(&Ok(Some(offset))), "OK 2:42");
+        assert_eq!(reply(&Ok(None)), "OK");
+        assert_eq!(reply(&Err(anyhow!("line\nbreak"))), "ERR line break");
+    }
+}
@@ -0,0 +1,512 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cli::{AckMode, MqttServer, TopicMapping};
+use crate::server::{Message, Server, SharedSink, backpressure};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use rumqttc::mqttbytes::v4;
+use rumqttc::v5::mqttbytes::v5;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    V4,
+    V5,
+}
+
+struct Publish {
+    topic: String,
+    qos: u8,
+    pkid: u16,
+    payload: Bytes,
+    user_properties: Vec<(String, String)>,
+}
+
+enum Incoming {
+    Connect(Version),
+    Publish(Publish),
+    PubRel(u16),
+    Subscribe(u16, usize),
+    Unsubscribe(u16, usize),
+    PingReq,
+    Disconnect,
+    Ignored,
+}
+
+enum Outgoing {
+    ConnAck,
+    PubAck(u16, bool),
+    PubRec(u16, bool),
+    PubComp(u16),
+    SubAck(u16, usize),
+    UnsubAck(u16, usize),
+    PingResp,
+}
+
+struct Context {
+    sink: SharedSink,
+    topic_mappings: Vec<TopicMapping>,
+    topic_header: Option<String>,
+    concurrency_limit: usize,
+    max_packet_size: usize,
+}
+
+fn protocol_level(buf: &[u8]) -> Option<u8> {
+    let mut offset = 1;
+    while *buf.get(offset)? & 0x80 != 0 {
+        offset += 1;
+    }
+    let name = offset + 1;
+    let name_len = u16::from_be_bytes([*buf.get(name)?, *buf.get(name + 1)?]) as usize;
+    buf.get(name + 2 + name_len).copied()
+}
+
+fn decode_v4(buf: &mut BytesMut, max_size: usize) -> Result<Option<Incoming>> {
+    let packet = match v4::Packet::read(buf, max_size) {
+        Ok(packet) => packet,
+        Err(rumqttc::mqttbytes::Error::InsufficientBytes(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let incoming = match packet {
+        v4::Packet::Connect(_) => Incoming::Connect(Version::V4),
+        v4::Packet::Publish(publish) => Incoming::Publish(Publish {
+            topic: publish.topic,
+            qos: publish.qos as u8,
+            pkid: publish.pkid,
+            payload: publish.payload,
+            user_properties: Vec::new(),
+        }),
+        v4::Packet::PubRel(pubrel) => Incoming::PubRel(pubrel.pkid),
+        v4::Packet::Subscribe(subscribe) => {
+            Incoming::Subscribe(subscribe.pkid, subscribe.filters.len())
+        }
+        v4::Packet::Unsubscribe(unsubscribe) => {
+            Incoming::Unsubscribe(unsubscribe.pkid, unsubscribe.topics.len())
+        }
+        v4::Packet::PingReq => Incoming::PingReq,
+        v4::Packet::Disconnect => Incoming::Disconnect,
+        _ => Incoming::Ignored,
+    };
+    Ok(Some(incoming))
+}
+
+fn decode_v5(buf: &mut BytesMut, max_size: usize) -> Result<Option<Incoming>> {
+    let packet = match v5::Packet::read(buf, Some(max_size as u32)) {
+        Ok(packet) => packet,
+        Err(rumqttc::v5::mqttbytes::Error::InsufficientBytes(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let incoming = match packet {
+        v5::Packet::Connect(..) => Incoming::Connect(Version::V5),
+        v5::Packet::Publish(publish) => Incoming::Publish(Publish {
+            topic: String::from_utf8(publish.topic.to_vec())?,
+            qos: publish.qos as u8,
+            pkid: publish.pkid,
+            payload: publish.payload,
+            user_properties: publish
+                .properties
+                .map(|properties| properties.user_properties)
+                .unwrap_or_default(),
+        }),
+        v5::Packet::PubRel(pubrel) => Incoming::PubRel(pubrel.pkid),
+        v5::Packet::Subscribe(subscribe) => {
+            Incoming::Subscribe(subscribe.pkid, subscribe.filters.len())
+        }
+        v5::Packet::Unsubscribe(unsubscribe) => {
+            Incoming::Unsubscribe(unsubscribe.pkid, unsubscribe.filters.len())
+        }
+        v5::Packet::PingReq(_) => Incoming::PingReq,
+        v5::Packet::Disconnect(_) => Incoming::Disconnect,
+        _ => Incoming::Ignored,
+    };
+    Ok(Some(incoming))
+}
+
+fn decode(
+    version: Option<Version>,
+    buf: &mut BytesMut,
+    max_size: usize,
+) -> Result<Option<Incoming>> {
+    match version {
+        Some(Version::V4) => decode_v4(buf, max_size),
+        Some(Version::V5) => decode_v5(buf, max_size),
+        None => match protocol_level(buf) {
+            None => Ok(None),
+            Some(4) => decode_v4(buf, max_size),
+            Some(5) => decode_v5(buf, max_size),
+            Some(level) => bail!("Unsupported MQTT protocol level {level}"),
+        },
+    }
+}
+
+fn encode_v4(outgoing: Outgoing, buf: &mut BytesMut) -> Result<()> {
+    let packet = match outgoing {
+        Outgoing::ConnAck => {
+            v4::Packet::ConnAck(v4::ConnAck::new(v4::ConnectReturnCode::Success, false))
+        }
+        Outgoing::PubAck(pkid, true) => v4::Packet::PubAck(v4::PubAck::new(pkid)),
+        Outgoing::PubRec(pkid, true) => v4::Packet::PubRec(v4::PubRec::new(pkid)),
+        Outgoing::PubAck(pkid, false) | Outgoing::PubRec(pkid, false) => {
+            bail!("Failed to produce message {pkid}")
+        }
+        Outgoing::PubComp(pkid) => v4::Packet::PubComp(v4::PubComp::new(pkid)),
+        Outgoing::SubAck(pkid, count) => v4::Packet::SubAck(v4::SubAck::new(
+            pkid,
+            vec![v4::SubscribeReasonCode::Failure; count],
+        )),
+        Outgoing::UnsubAck(pkid, _) => v4::Packet::UnsubAck(v4::UnsubAck::new(pkid)),
+        Outgoing::PingResp => v4::Packet::PingResp,
+    };
+    packet.write(buf, usize::MAX)?;
+    Ok(())
+}
+
+fn encode_v5(outgoing: Outgoing, buf: &mut BytesMut) -> Result<()> {
+    let packet = match outgoing {
+        Outgoing::ConnAck => v5::Packet::ConnAck(v5::ConnAck {
+            session_present: false,
+            code: v5::ConnectReturnCode::Success,
+            properties: None,
+        }),
+        Outgoing::PubAck(pkid, delivered) => v5::Packet::PubAck(v5::PubAck {
+            pkid,
+            reason: match delivered {
+                true => v5::PubAckReason::Success,
+                false => v5::PubAckReason::UnspecifiedError,
+            },
+            properties: None,
+        }),
+        Outgoing::PubRec(pkid, delivered) => v5::Packet::PubRec(v5::PubRec {
+            pkid,
+            reason: match delivered {
+                true => v5::PubRecReason::Success,
+                false => v5::PubRecReason::UnspecifiedError,
+            },
+            properties: None,
+        }),
+        Outgoing::PubComp(pkid) => v5::Packet::PubComp(v5::PubComp::new(pkid, None)),
+        Outgoing::SubAck(pkid, count) => v5::Packet::SubAck(v5::SubAck {
+            pkid,
+            return_codes: vec![v5::SubscribeReasonCode::ImplementationSpecific; count],
+            properties: None,
+        }),
+        Outgoing::UnsubAck(pkid, count) => v5::Packet::UnsubAck(v5::UnsubAck {
+            pkid,
+            reasons: vec![v5::UnsubAckReason::NoSubscriptionExisted; count],
+            properties: None,
+        }),
+        Outgoing::PingResp => v5::Packet::PingResp(v5::PingResp),
+    };
+    packet.write(buf, None)?;
+    Ok(())
+}
+
+fn encode(version: Version, outgoing: Outgoing, buf: &mut BytesMut) -> Result<()> {
+    match version {
+        Version::V4 => encode_v4(outgoing, buf),
+        Version::V5 => encode_v5(outgoing, buf),
+    }
+}
+
+async fn read<R>(
+    reader: &mut R,
+    version: Option<Version>,
+    buf: &mut BytesMut,
+    max_size: usize,
+) -> Result<Option<Incoming>>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        if let Some(incoming) = decode(version, buf, max_size)? {
+            return Ok(Some(incoming));
+        }
+        if reader.read_buf(buf).await? == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+async fn write<W>(writer: &mut W, version: Version, outgoing: Outgoing) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = BytesMut::new();
+    encode(version, outgoing, &mut buf)?;
+    writer.write_all(&buf).await?;
+    Ok(())
+}
+
+async fn publish(context: Arc<Context>, publish: Publish) -> Option<Outgoing> {
+    let topic = context
+        .topic_mappings
+        .iter()
+        .find(|mapping| mapping.matches(&publish.topic))
+        .map(|mapping| mapping.topic.as_str());
+    let mut headers: Vec<(&str, &[u8])> = publish
+        .user_properties
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_bytes()))
+        .collect();
+    if let Some(header) = &context.topic_header {
+        headers.push((header.as_str(), publish.topic.as_bytes()));
+    }
+    let message = Message {
+        topic,
+        key: None,
+        headers,
+        payload: &publish.payload,
+    };
+    let ack_mode = match publish.qos {
+        0 => AckMode::Enqueued,
+        _ => AckMode::Delivered,
+    };
+    let result = context.sink.send_with_ack(message, ack_mode).await;
+    if let Err(e) = &result {
+        log::warn!("{e}");
+    }
+    match publish.qos {
+        0 => None,
+        1 => Some(Outgoing::PubAck(publish.pkid, result.is_ok())),
+        _ => Some(Outgoing::PubRec(publish.pkid, result.is_ok())),
+    }
+}
+
+async fn connection<S>(
+    stream: S,
+    context: Arc<Context>,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buf = BytesMut::with_capacity(4096);
+    let version = match read(&mut reader, None, &mut buf, context.max_packet_size).await? {
+        Some(Incoming::Connect(version)) => version,
+        Some(_) => bail!("Expected CONNECT packet"),
+        None => return Ok(()),
+    };
+    write(&mut writer, version, Outgoing::ConnAck).await?;
+
+    let (pending_snd, mut pending_rcv) =
+        mpsc::channel::<JoinHandle<Option<Outgoing>>>(context.concurrency_limit.max(1));
+    let mut replies = tokio::spawn(async move {
+        while let Some(pending) = pending_rcv.recv().await {
+            if let Some(outgoing) = pending.await? {
+                write(&mut writer, version, outgoing).await?;
+            }
+        }
+        anyhow::Ok(())
+    });
+
+    loop {
+        let incoming = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            result = &mut replies => return result?,
+            incoming = async {
+                backpressure(&context.sink).await;
+                read(&mut reader, Some(version), &mut buf, context.max_packet_size).await
+            } => incoming?,
+        };
+        let pending = match incoming {
+            None | Some(Incoming::Disconnect) => break,
+            Some(Incoming::Publish(p)) => tokio::spawn(publish(context.clone(), p)),
+            Some(incoming) => {
+                let outgoing = match incoming {
+                    Incoming::Connect(_) => bail!("Unexpected CONNECT packet"),
+                    Incoming::PubRel(pkid) => Some(Outgoing::PubComp(pkid)),
+                    Incoming::Subscribe(pkid, count) => Some(Outgoing::SubAck(pkid, count)),
+                    Incoming::Unsubscribe(pkid, count) => Some(Outgoing::UnsubAck(pkid, count)),
+                    Incoming::PingReq => Some(Outgoing::PingResp),
+                    _ => None,
+                };
+                tokio::spawn(async move { outgoing })
+            }
+        };
+        if pending_snd.send(pending).await.is_err() {
+            break;
+        }
+    }
+    drop(pending_snd);
+    replies.await?
+}
+
+#[async_trait]
+impl Server for MqttServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        mut shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let context = Arc::new(Context {
+            sink,
+            topic_mappings: self.topic_mappings.clone(),
+            topic_header: self.topic_header.clone(),
+            concurrency_limit: self.concurrency_limit,
+            max_packet_size: self.max_packet_size,
+        });
+        let listener = TcpListener::bind(self.address).await?;
+        loop {
+            let (stream, _) = tokio::select! {
+                _ = shutdown_trigger_receiver.recv() => break,
+                accepted = listener.accept() => accepted?,
+            };
+            let context = context.clone();
+            let shutdown_trigger_receiver = shutdown_trigger_receiver.resubscribe();
+            tokio::spawn(async move {
+                if let Err(e) = connection(stream, context, shutdown_trigger_receiver).await {
+                    log::warn!("{e}");
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{MqttServer, TopicMapping};
+    use crate::server::tests::{RunningServer, free_tcp_address};
+    use rumqttc::v5::mqttbytes::v5::PublishProperties;
+    use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+    use std::time::Duration;
+
+    fn server(address: std::net::SocketAddr) -> MqttServer {
+        MqttServer {
+            address,
+            concurrency_limit: 16,
+            max_packet_size: 1024,
+            topic_mappings: vec![TopicMapping {
+                filter: "sensors/+/temperature".to_string(),
+                topic: "temperatures".to_string(),
+            }],
+            topic_header: Some("mqtt_topic".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(server(address));
+
+        let options = MqttOptions::new("client", address.ip().to_string(), address.port());
+        let (client, mut event_loop) = AsyncClient::new(options, 10);
+        client
+            .publish("sensors/1/temperature", QoS::AtLeastOnce, false, "21.5")
+            .await
+            .unwrap();
+        client
+            .publish("other", QoS::AtMostOnce, false, "message")
+            .await
+            .unwrap();
+        let acknowledged = async {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::PubAck(_))) => break,
+                    Ok(_) => (),
+                    Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), acknowledged)
+            .await
+            .unwrap();
+
+        let messages = server.sink.wait_for(2).await;
+        assert_eq!(messages, vec![b"21.5".to_vec(), b"message".to_vec()]);
+        assert_eq!(
+            server.sink.topics().await,
+            vec![Some("temperatures".to_string()), None]
+        );
+        assert_eq!(
+            server.sink.headers().await[0],
+            vec![("mqtt_topic".to_string(), b"sensors/1/temperature".to_vec())]
+        );
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_publish_v5() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(server(address));
+
+        let options =
+            rumqttc::v5::MqttOptions::new("client", address.ip().to_string(), address.port());
+        let (client, mut event_loop) = rumqttc::v5::AsyncClient::new(options, 10);
+        let properties = PublishProperties {
+            user_properties: vec![("device".to_string(), "1".to_string())],
+            ..PublishProperties::default()
+        };
+        client
+            .publish_with_properties(
+                "sensors/1/temperature",
+                rumqttc::v5::mqttbytes::QoS::AtLeastOnce,
+                false,
+                "21.5",
+                properties,
+            )
+            .await
+            .unwrap();
+        let acknowledged = async {
+            loop {
+                match event_loop.poll().await {
+                    Ok(rumqttc::v5::Event::Incoming(rumqttc::v5::Incoming::PubAck(_))) => break,
+                    Ok(_) => (),
+                    Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), acknowledged)
+            .await
+            .unwrap();
+
+        assert_eq!(server.sink.messages().await, vec![b"21.5".to_vec()]);
+        assert_eq!(
+            server.sink.headers().await[0],
+            vec![
+                ("device".to_string(), b"1".to_vec()),
+                ("mqtt_topic".to_string(), b"sensors/1/temperature".to_vec())
+            ]
+        );
+
+        server.stop().await;
+    }
+
+    #[test]
+    fn test_topic_mapping() {
+        let mapping = |filter: &str| TopicMapping {
+            filter: filter.to_string(),
+            topic: "topic".to_string(),
+        };
+        assert!(mapping("a/+/c").matches("a/b/c"));
+        assert!(!mapping("a/+/c").matches("a/b/d"));
+        assert!(!mapping("a/+").matches("a/b/c"));
+        assert!(mapping("a/#").matches("a"));
+        assert!(mapping("a/#").matches("a/b/c"));
+        assert!(!mapping("a/b").matches("a"));
+    }
+}
@@ -0,0 +1,641 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Read;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Request, State};
+use axum::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use flate2::read::MultiGzDecoder;
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsPartialSuccess, ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsPartialSuccess, ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    ExportTracePartialSuccess, ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use opentelemetry_proto::tonic::metrics::v1::Metric;
+use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+use prost::Message as _;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc::Sender;
+use tonic::codec::CompressionEncoding;
+use tonic::server::Grpc;
+use tonic::{Code, Status};
+use tonic_prost::ProstCodec;
+
+use crate::cli::OtlpServer;
+use crate::server::{Message, Server, SharedSink};
+use crate::tls::TlsAcceptor;
+
+const PROTOBUF: &str = "application/x-protobuf";
+const JSON: &str = "application/json";
+
+#[derive(Clone)]
+struct OtlpState {
+    sink: SharedSink,
+    logs_topic: String,
+    traces_topic: String,
+    metrics_topic: String,
+    split: bool,
+    max_message_size: usize,
+}
+
+enum Signal {
+    Logs,
+    Traces,
+    Metrics,
+}
+
+trait Export: prost::Message + Default + DeserializeOwned + Send + 'static {
+    const SIGNAL: Signal;
+
+    type Response: prost::Message + Default + Serialize + Send + 'static;
+
+    /// The records produced with `--split`, together with the number of items each contains.
+    fn records(&self) -> Vec<(Value, i64)>;
+
+    fn partial_success(rejected: i64, error_message: String) -> Self::Response;
+}
+
+impl Export for ExportLogsServiceRequest {
+    const SIGNAL: Signal = Signal::Logs;
+
+    type Response = ExportLogsServiceResponse;
+
+    fn records(&self) -> Vec<(Value, i64)> {
+        let mut records = Vec::new();
+        for resource_logs in &self.resource_logs {
+            for scope_logs in &resource_logs.scope_logs {
+                for log_record in &scope_logs.log_records {
+                    records.push((
+                        json!({
+                            "resource": resource_logs.resource,
+                            "scope": scope_logs.scope,
+                            "logRecord": log_record,
+                        }),
+                        1,
+                    ));
+                }
+            }
+        }
+        records
+    }
+
+    fn partial_success(rejected: i64, error_message: String) -> Self::Response {
+        ExportLogsServiceResponse {
+            partial_success: Some(ExportLogsPartialSuccess {
+                rejected_log_records: rejected,
+                error_message,
+            }),
+        }
+    }
+}
+
+impl Export for ExportTraceServiceRequest {
+    const SIGNAL: Signal = Signal::Traces;
+
+    type Response = ExportTraceServiceResponse;
+
+    fn records(&self) -> Vec<(Value, i64)> {
+        let mut records = Vec::new();
+        for resource_spans in &self.resource_spans {
+            for scope_spans in &resource_spans.scope_spans {
+                for span in &scope_spans.spans {
+                    records.push((
+                        json!({
+                            "resource": resource_spans.resource,
+                            "scope": scope_spans.scope,
+                            "span": span,
+                        }),
+                        1,
+                    ));
+                }
+            }
+        }
+        records
+    }
+
+    fn partial_success(rejected: i64, error_message: String) -> Self::Response {
+        ExportTraceServiceResponse {
+            partial_success: Some(ExportTracePartialSuccess {
+                rejected_spans: rejected,
+                error_message,
+            }),
+        }
+    }
+}
+
+impl Export for ExportMetricsServiceRequest {
+    const SIGNAL: Signal = Signal::Metrics;
+
+    type Response = ExportMetricsServiceResponse;
+
+    fn records(&self) -> Vec<(Value, i64)> {
+        let mut records = Vec::new();
+        for resource_metrics in &self.resource_metrics {
+            for scope_metrics in &resource_metrics.scope_metrics {
+                for metric in &scope_metrics.metrics {
+                    records.push((
+                        json!({
+                            "resource": resource_metrics.resource,
+                            "scope": scope_metrics.scope,
+                            "metric": metric,
+                        }),
+                        data_points(metric),
+                    ));
+                }
+            }
+        }
+        records
+    }
+
+    fn partial_success(rejected: i64, error_message: String) -> Self::Response {
+        ExportMetricsServiceResponse {
+            partial_success: Some(ExportMetricsPartialSuccess {
+                rejected_data_points: rejected,
+                error_message,
+            }),
+        }
+    }
+}
+
+fn data_points(metric: &Metric) -> i64 {
+    let data_points = match &metric.data {
+        Some(Data::Gauge(gauge)) => gauge.data_points.len(),
+        Some(Data::Sum(sum)) => sum.data_points.len(),
+        Some(Data::Histogram(histogram)) => histogram.data_points.len(),
+        Some(Data::ExponentialHistogram(histogram)) => histogram.data_points.len(),
+        Some(Data::Summary(summary)) => summary.data_points.len(),
+        None => 0,
+    };
+    data_points as i64
+}
+
+impl OtlpState {
+    fn topic(&self, signal: Signal) -> &str {
+        match signal {
+            Signal::Logs => &self.logs_topic,
+            Signal::Traces => &self.traces_topic,
+            Signal::Metrics => &self.metrics_topic,
+        }
+    }
+
+    /// Produces the request, returns the partial success response if some records failed.
+    async fn export<E: Export>(
+        &self,
+        request: E,
+    ) -> std::result::Result<Option<E::Response>, Status> {
+        if self.sink.saturated() {
+            self.sink.rejected("backpressure");
+            return Err(Status::unavailable("backpressure"));
+        }
+        let payloads: Vec<(Vec<u8>, i64)> = match self.split {
+            false => vec![(request.encode_to_vec(), 0)],
+            true => request
+                .records()
+                .iter()
+                .map(|(record, items)| Ok((serde_json::to_vec(record)?, *items)))
+                .collect::<serde_json::Result<_>>()
+                .map_err(|e| Status::internal(e.to_string()))?,
+        };
+        let topic = self.topic(E::SIGNAL);
+        let sends = payloads.iter().map(|(payload, _)| {
+            self.sink.send_message(Message {
+                topic: Some(topic),
+                key: None,
+                headers: Vec::new(),
+                payload,
+            })
+        });
+        // Records that were delivered must not be retried, so failures of
+        // single records are reported as a partial success.
+        let results = futures::future::join_all(sends).await;
+        let mut failed = 0;
+        let mut rejected = 0;
+        let mut error = None;
+        for ((_, items), result) in payloads.iter().zip(results) {
+            if let Err(e) = result {
+                log::warn!("{e}");
+                failed += 1;
+                rejected += items;
+                error.get_or_insert_with(|| e.to_string());
+            }
+        }
+        match error {
+            None => Ok(None),
+            Some(error) if failed == payloads.len() => Err(Status::internal(error)),
+            Some(error) => Ok(Some(E::partial_success(rejected, error))),
+        }
+    }
+
+    fn decompress(&self, headers: &HeaderMap, body: Bytes) -> Result<Bytes> {
+        match headers
+            .get(CONTENT_ENCODING)
+            .map(|encoding| encoding.as_bytes())
+        {
+            Some(b"gzip") => {
+                let mut decompressed = Vec::new();
+                MultiGzDecoder::new(body.as_ref())
+                    .take(self.max_message_size as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                if decompressed.len() > self.max_message_size {
+                    anyhow::bail!("Request exceeds {} bytes", self.max_message_size);
+                }
+                Ok(decompressed.into())
+            }
+            _ => Ok(body),
+        }
+    }
+}
+
+fn status_code(status: &Status) -> StatusCode {
+    match status.code() {
+        Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn http_handler<E: Export>(
+    State(state): State<OtlpState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let json = headers
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(JSON.as_bytes()));
+    let request = state
+        .decompress(&headers, body)
+        .and_then(|body| match json {
+            true => Ok(serde_json::from_slice::<E>(&body)?),
+            false => Ok(E::decode(body)?),
+        });
+    let result = match request {
+        Ok(request) => state.export(request).await,
+        Err(e) => Err(Status::invalid_argument(e.to_string())),
+    };
+    match (result, json) {
+        (Ok(None), true) => ([(CONTENT_TYPE, JSON)], "{}".to_string()).into_response(),
+        (Ok(Some(response)), true) => match serde_json::to_string(&response) {
+            Ok(response) => ([(CONTENT_TYPE, JSON)], response).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+        (Ok(response), false) => (
+            [(CONTENT_TYPE, PROTOBUF)],
+            response.unwrap_or_default().encode_to_vec(),
+        )
+            .into_response(),
+        (Err(status), _) => (status_code(&status), status.message().to_string()).into_response(),
+    }
+}
+
+async fn grpc_handler<E: Export>(State(state): State<OtlpState>, request: Request) -> Response {
+    let max_message_size = state.max_message_size;
+    let service = tower::service_fn(move |request: tonic::Request<E>| {
+        let state = state.clone();
+        async move {
+            let response = state.export(request.into_inner()).await?;
+            Ok(tonic::Response::new(response.unwrap_or_default()))
+        }
+    });
+    Grpc::new(ProstCodec::<E::Response, E>::default())
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(max_message_size)
+        .unary(service, request)
+        .await
+        .into_response()
+}
+
+#[async_trait]
+impl Server for OtlpServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        mut shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let state = OtlpState {
+            sink,
+            logs_topic: self.logs_topic.clone(),
+            traces_topic: self.traces_topic.clone(),
+            metrics_topic: self.metrics_topic.clone(),
+            split: self.split,
+            max_message_size: self.max_message_size,
+        };
+        let http_app = Router::new()
+            .route("/v1/logs", post(http_handler::<ExportLogsServiceRequest>))
+            .route(
+                "/v1/traces",
+                post(http_handler::<ExportTraceServiceRequest>),
+            )
+            .route(
+                "/v1/metrics",
+                post(http_handler::<ExportMetricsServiceRequest>),
+            )
+            .layer(DefaultBodyLimit::max(self.max_message_size))
+            .with_state(state.clone());
+        let grpc_app = Router::new()
+            .route(
+                "/opentelemetry.proto.collector.logs.v1.LogsService/Export",
+                post(grpc_handler::<ExportLogsServiceRequest>),
+            )
+            .route(
+                "/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+                post(grpc_handler::<ExportTraceServiceRequest>),
+            )
+            .route(
+                "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export",
+                post(grpc_handler::<ExportMetricsServiceRequest>),
+            )
+            .layer(DefaultBodyLimit::max(self.max_message_size))
+            .with_state(state);
+
+        let tls = TlsAcceptor::http(&self.tls)?;
+        let http = TcpListener::bind(&self.address).await?;
+        let grpc = TcpListener::bind(&self.grpc_address).await?;
+        let mut grpc_shutdown_trigger_receiver = shutdown_trigger_receiver.resubscribe();
+        tokio::try_join!(
+            crate::tls::serve(http, tls.clone(), http_app, async move {
+                let _ = shutdown_trigger_receiver.recv().await;
+            }),
+            crate::tls::serve(grpc, tls, grpc_app, async move {
+                let _ = grpc_shutdown_trigger_receiver.recv().await;
+            }),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use async_trait::async_trait;
+
+    use super::OtlpState;
+    use crate::cli::{OtlpServer, Tls};
+    use crate::server::tests::{RecordingSink, RunningServer, free_tcp_address, retry};
+    use crate::server::{Message, MessageSink};
+    use axum::http::uri::PathAndQuery;
+    use opentelemetry_proto::tonic::collector::logs::v1::{
+        ExportLogsServiceRequest, ExportLogsServiceResponse,
+    };
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span};
+    use prost::Message;
+    use reqwest::StatusCode;
+    use serde_json::{Value, json};
+    use tonic::client::Grpc;
+    use tonic::transport::Channel;
+    use tonic_prost::ProstCodec;
+
+    fn server(split: bool) -> OtlpServer {
+        OtlpServer {
+            address: free_tcp_address(),
+            grpc_address: free_tcp_address(),
+            logs_topic: "logs".to_string(),
+            traces_topic: "traces".to_string(),
+            metrics_topic: "otlp_metrics".to_string(),
+            split,
+            max_message_size: 1024 * 1024,
+            tls: Tls::default(),
+        }
+    }
+
+    fn logs() -> ExportLogsServiceRequest {
+        let log_record = |severity_text: &str| LogRecord {
+            severity_text: severity_text.to_string(),
+            ..LogRecord::default()
+        };
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![log_record("INFO"), log_record("WARN")],
+                    ..ScopeLogs::default()
+                }],
+                ..ResourceLogs::default()
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http() {
+        let otlp = server(false);
+        let url = format!("http://{}/v1/logs", otlp.address);
+        let grpc_url = format!(
+            "http://{}/opentelemetry.proto.collector.logs.v1.LogsService/Export",
+            otlp.address
+        );
+        let grpc_address_url = format!("http://{}/v1/logs", otlp.grpc_address);
+        let server = RunningServer::start(otlp);
+
+        let client = reqwest::Client::new();
+        let response = retry(|| async {
+            Ok(client
+                .post(&url)
+                .header("content-type", "application/x-protobuf")
+                .body(logs().encode_to_vec())
+                .send()
+                .await?)
+        })
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(r#"{"resourceLogs":[{"scopeLogs":[{"logRecords":[{"severityText":"INFO"}]}]}]}"#)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "{}");
+        let response = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body("invalid")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let messages = server.sink.messages().await;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            ExportLogsServiceRequest::decode(messages[0].as_slice()).unwrap(),
+            logs()
+        );
+        assert_eq!(
+            ExportLogsServiceRequest::decode(messages[1].as_slice())
+                .unwrap()
+                .resource_logs[0]
+                .scope_logs[0]
+                .log_records[0]
+                .severity_text,
+            "INFO"
+        );
+        assert_eq!(
+            server.sink.topics().await,
+            vec![Some("logs".to_string()); 2]
+        );
+        let response = client.post(&grpc_url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = client.post(&grpc_address_url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        server.stop().await;
+    }
+
+    /// Fails every record with a `WARN` severity.
+    #[derive(Default)]
+    struct WarnFailingSink(RecordingSink);
+
+    #[async_trait]
+    impl MessageSink for WarnFailingSink {
+        fn topic(&self) -> String {
+            self.0.topic()
+        }
+
+        async fn send_message(&self, message: Message<'_>) -> Result<()> {
+            if message.payload.windows(4).any(|window| window == b"WARN") {
+                anyhow::bail!("Failed to produce");
+            }
+            self.0.send_message(message).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_success() {
+        let sink = Arc::new(WarnFailingSink::default());
+        let state = OtlpState {
+            sink: sink.clone(),
+            logs_topic: "logs".to_string(),
+            traces_topic: "traces".to_string(),
+            metrics_topic: "otlp_metrics".to_string(),
+            split: true,
+            max_message_size: 1024 * 1024,
+        };
+
+        let response = state.export(logs()).await.unwrap().unwrap();
+        let partial_success = response.partial_success.unwrap();
+        assert_eq!(partial_success.rejected_log_records, 1);
+        assert_eq!(partial_success.error_message, "Failed to produce");
+        assert_eq!(sink.0.messages().await.len(), 1);
+
+        let mut warn = logs();
+        warn.resource_logs[0].scope_logs[0].log_records.remove(0);
+        let status = state.export(warn).await.unwrap_err();
+        assert_eq!(status.message(), "Failed to produce");
+
+        let state = OtlpState {
+            split: false,
+            ..state
+        };
+        assert!(state.export(logs()).await.is_err());
+        assert_eq!(sink.0.messages().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_split() {
+        let otlp = server(true);
+        let address = otlp.grpc_address;
+        let server = RunningServer::start(otlp);
+
+        let channel = retry(|| async {
+            Ok(Channel::from_shared(format!("http://{address}"))?
+                .connect()
+                .await?)
+        })
+        .await;
+        let mut client = Grpc::new(channel);
+        client.ready().await.unwrap();
+        let _: tonic::Response<ExportLogsServiceResponse> = client
+            .unary(
+                tonic::Request::new(logs()),
+                PathAndQuery::from_static(
+                    "/opentelemetry.proto.collector.logs.v1.LogsService/Export",
+                ),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap();
+        let traces = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        name: "span".to_string(),
+                        ..Span::default()
+                    }],
+                    ..ScopeSpans::default()
+                }],
+                ..ResourceSpans::default()
+            }],
+        };
+        client.ready().await.unwrap();
+        let _: tonic::Response<ExportTraceServiceResponse> = client
+            .unary(
+                tonic::Request::new(traces),
+                PathAndQuery::from_static(
+                    "/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+                ),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap();
+
+        let messages: Vec<Value> = server
+            .sink
+            .messages()
+            .await
+            .iter()
+            .map(|message| serde_json::from_slice(message).unwrap())
+            .collect();
+        assert_eq!(messages.len(), 3);
+        let mut severities: Vec<&Value> = messages[..2]
+            .iter()
+            .map(|message| &message["logRecord"]["severityText"])
+            .collect();
+        severities.sort_by_key(|severity| severity.to_string());
+        assert_eq!(severities, [&json!("INFO"), &json!("WARN")]);
+        assert_eq!(messages[2]["span"]["name"], "span");
+        assert_eq!(
+            server.sink.topics().await,
+            vec![
+                Some("logs".to_string()),
+                Some("logs".to_string()),
+                Some("traces".to_string())
+            ]
+        );
+
+        server.stop().await;
+    }
+}
@@ -0,0 +1,189 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cli::{PrincipalRateLimit, RateLimit};
+
+const MAX_BUCKETS: usize = 10000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Limit<K> {
+    rate: f64,
+    burst: f64,
+    buckets: HashMap<K, Bucket>,
+}
+
+struct Limits {
+    global: Option<Limit<()>>,
+    per_ip: Option<Limit<IpAddr>>,
+    per_principal: Option<Limit<String>>,
+}
+
+pub struct RateLimiter {
+    limits: Mutex<Limits>,
+}
+
+impl Bucket {
+    fn refill(&mut self, rate: f64, burst: f64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.updated = now;
+    }
+}
+
+impl<K: Hash + Eq> Limit<K> {
+    fn new(rate: Option<f64>, burst: Option<u32>) -> Option<Limit<K>> {
+        let rate = rate?;
+        let burst = burst.map_or(rate.ceil(), f64::from).max(1.0);
+        Some(Limit {
+            rate,
+            burst,
+            buckets: HashMap::new(),
+        })
+    }
+
+    fn evict(&mut self) {
+        // Drop the least recently updated half, so this only runs every MAX_BUCKETS / 2 new keys
+        let mut updated: Vec<Instant> =
+            self.buckets.values().map(|bucket| bucket.updated).collect();
+        let (_, cutoff, _) = updated.select_nth_unstable(updated.len() / 2);
+        let cutoff = *cutoff;
+        self.buckets.retain(|_, bucket| bucket.updated > cutoff);
+    }
+
+    fn bucket(&mut self, key: K, now: Instant) -> (f64, &mut Bucket) {
+        if self.buckets.len() >= MAX_BUCKETS && !self.buckets.contains_key(&key) {
+            self.evict();
+        }
+        let bucket = self.buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.refill(self.rate, self.burst, now);
+        (self.rate, bucket)
+    }
+}
+
+impl RateLimiter {
+    fn build(cfg: &RateLimit, per_principal: Option<f64>) -> Option<Arc<RateLimiter>> {
+        let limits = Limits {
+            global: Limit::new(cfg.rate_limit, cfg.rate_limit_burst),
+            per_ip: Limit::new(cfg.rate_limit_per_ip, cfg.rate_limit_burst),
+            per_principal: Limit::new(per_principal, cfg.rate_limit_burst),
+        };
+        match (&limits.global, &limits.per_ip, &limits.per_principal) {
+            (None, None, None) => None,
+            _ => Some(Arc::new(RateLimiter {
+                limits: Mutex::new(limits),
+            })),
+        }
+    }
+
+    pub fn new(cfg: &RateLimit) -> Option<Arc<RateLimiter>> {
+        RateLimiter::build(cfg, None)
+    }
+
+    pub fn with_principals(cfg: &PrincipalRateLimit) -> Option<Arc<RateLimiter>> {
+        RateLimiter::build(&cfg.rate_limit, cfg.rate_limit_per_principal)
+    }
+
+    pub fn check(&self, ip: Option<IpAddr>, principal: Option<&str>) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+        let Limits {
+            global,
+            per_ip,
+            per_principal,
+        } = &mut *limits;
+        let mut buckets = Vec::with_capacity(3);
+        if let (Some(limit), Some(principal)) = (per_principal, principal) {
+            buckets.push(limit.bucket(principal.to_string(), now));
+        }
+        if let (Some(limit), Some(ip)) = (per_ip, ip) {
+            buckets.push(limit.bucket(ip, now));
+        }
+        if let Some(limit) = global {
+            buckets.push(limit.bucket((), now));
+        }
+        // Only take tokens once all limits allow the message
+        let retry_after = buckets
+            .iter()
+            .filter(|(_, bucket)| bucket.tokens < 1.0)
+            .map(|(rate, bucket)| Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+            .max();
+        if let Some(retry_after) = retry_after {
+            return Err(retry_after);
+        }
+        for (_, bucket) in buckets {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    pub async fn wait(&self, ip: Option<IpAddr>, principal: Option<&str>) {
+        while let Err(retry_after) = self.check(ip, principal) {
+            tokio::time::sleep(retry_after).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+    use crate::cli::{PrincipalRateLimit, RateLimit};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_rate_limiter() {
+        assert!(RateLimiter::new(&RateLimit::default()).is_none());
+
+        let limiter = RateLimiter::new(&RateLimit {
+            rate_limit_per_ip: Some(1.0),
+            rate_limit_burst: Some(2),
+            ..RateLimit::default()
+        })
+        .unwrap();
+        let first = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let second = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+        assert!(limiter.check(first, None).is_ok());
+        assert!(limiter.check(first, None).is_ok());
+        let retry_after = limiter.check(first, None).unwrap_err();
+        assert!(retry_after.as_secs_f64() > 0.9 && retry_after.as_secs_f64() <= 1.0);
+        assert!(limiter.check(second, None).is_ok());
+        assert!(limiter.check(None, Some("principal")).is_ok());
+
+        let limiter = RateLimiter::with_principals(&PrincipalRateLimit {
+            rate_limit: RateLimit {
+                rate_limit_per_ip: Some(1.0),
+                ..RateLimit::default()
+            },
+            rate_limit_per_principal: Some(1.0),
+        })
+        .unwrap();
+        assert!(limiter.check(first, Some("first")).is_ok());
+        assert!(limiter.check(first, Some("second")).is_err());
+        assert!(limiter.check(second, Some("second")).is_ok());
+    }
+}
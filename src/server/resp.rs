@@ -0,0 +1,566 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{Map, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+use crate::cli::{AckMode, RespServer};
+use crate::server::{Message, Offset, Server, SharedSink, backpressure};
+use crate::tls::{ClientSubject, TlsAcceptor, serve_streams};
+
+type Command = Vec<Vec<u8>>;
+
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Simple(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Array(Vec<Reply>),
+    Map(Vec<(Reply, Reply)>),
+}
+
+enum Pending {
+    Ready(Reply),
+    Produce(JoinHandle<Reply>),
+    Quit,
+}
+
+struct Record {
+    topic: Option<String>,
+    key: Option<Vec<u8>>,
+    payload: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct Context {
+    sink: SharedSink,
+    concurrency_limit: usize,
+    max_message_size: usize,
+    ack_mode: AckMode,
+    topic_prefix: Option<String>,
+    key_by_name: bool,
+    subject_header: Option<String>,
+}
+
+impl Reply {
+    fn bulk(value: &str) -> Reply {
+        Reply::Bulk(value.as_bytes().to_vec())
+    }
+
+    fn encode(&self, resp3: bool, buf: &mut Vec<u8>) {
+        match self {
+            Reply::Simple(value) => buf.extend_from_slice(format!("+{value}\r\n").as_bytes()),
+            Reply::Error(e) => {
+                let e = e.replace(['\r', '\n'], " ");
+                buf.extend_from_slice(format!("-{e}\r\n").as_bytes());
+            }
+            Reply::Integer(value) => buf.extend_from_slice(format!(":{value}\r\n").as_bytes()),
+            Reply::Bulk(value) => {
+                buf.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+                buf.extend_from_slice(value);
+                buf.extend_from_slice(b"\r\n");
+            }
+            Reply::Array(values) => {
+                buf.extend_from_slice(format!("*{}\r\n", values.len()).as_bytes());
+                for value in values {
+                    value.encode(resp3, buf);
+                }
+            }
+            Reply::Map(entries) => {
+                let header = match resp3 {
+                    true => format!("%{}\r\n", entries.len()),
+                    false => format!("*{}\r\n", 2 * entries.len()),
+                };
+                buf.extend_from_slice(header.as_bytes());
+                for (key, value) in entries {
+                    key.encode(resp3, buf);
+                    value.encode(resp3, buf);
+                }
+            }
+        }
+    }
+}
+
+fn line(buf: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let end = pos + buf.get(pos..)?.windows(2).position(|w| w == b"\r\n")?;
+    Some((&buf[pos..end], end + 2))
+}
+
+fn integer(line: &[u8]) -> Option<i64> {
+    std::str::from_utf8(line).ok()?.parse().ok()
+}
+
+fn parse(buf: &[u8]) -> Result<Option<(Command, usize)>> {
+    if buf.first() != Some(&b'*') {
+        let Some(end) = buf.iter().position(|b| *b == b'\n') else {
+            return Ok(None);
+        };
+        let command = buf[..end]
+            .split(u8::is_ascii_whitespace)
+            .filter(|arg| !arg.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect();
+        return Ok(Some((command, end + 1)));
+    }
+    let Some((count, mut pos)) = line(buf, 1) else {
+        return Ok(None);
+    };
+    let Some(count) = integer(count) else {
+        bail!("invalid multibulk length");
+    };
+    let mut command = Vec::new();
+    for _ in 0..count {
+        let Some((header, start)) = line(buf, pos) else {
+            return Ok(None);
+        };
+        let Some(len) = header.strip_prefix(b"$") else {
+            bail!("expected '$'");
+        };
+        let Some(len) = integer(len).and_then(|len| usize::try_from(len).ok()) else {
+            bail!("invalid bulk length");
+        };
+        let end = start.saturating_add(len);
+        let Some(arg) = buf.get(start..end) else {
+            return Ok(None);
+        };
+        match buf.get(end..end + 2) {
+            None => return Ok(None),
+            Some(b"\r\n") => (),
+            Some(_) => bail!("expected CRLF after bulk string"),
+        }
+        command.push(arg.to_vec());
+        pos = end + 2;
+    }
+    Ok(Some((command, pos)))
+}
+
+async fn read_command<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max_message_size: usize,
+) -> Result<Option<Command>>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        if let Some((command, len)) = parse(buf)? {
+            buf.drain(..len);
+            return Ok(Some(command));
+        }
+        if buf.len() >= max_message_size {
+            bail!("Command exceeds {max_message_size} bytes");
+        }
+        buf.reserve(8192);
+        if reader.read_buf(buf).await? == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            bail!("Connection closed within a command");
+        }
+    }
+}
+
+fn hello(args: &[Vec<u8>], resp3: &mut bool) -> Reply {
+    match args.first().map(Vec::as_slice) {
+        None => (),
+        Some(b"2") => *resp3 = false,
+        Some(b"3") => *resp3 = true,
+        Some(_) => return Reply::Error("NOPROTO unsupported protocol version".to_string()),
+    }
+    Reply::Map(vec![
+        (Reply::bulk("server"), Reply::bulk("kafka-proxy")),
+        (
+            Reply::bulk("version"),
+            Reply::bulk(env!("CARGO_PKG_VERSION")),
+        ),
+        (
+            Reply::bulk("proto"),
+            Reply::Integer(if *resp3 { 3 } else { 2 }),
+        ),
+        (Reply::bulk("mode"), Reply::bulk("standalone")),
+        (Reply::bulk("role"), Reply::bulk("master")),
+        (Reply::bulk("modules"), Reply::Array(Vec::new())),
+    ])
+}
+
+fn xadd(args: &[Vec<u8>]) -> Option<(Option<String>, Map<String, Value>)> {
+    let mut i = 0;
+    loop {
+        let arg = args.get(i)?;
+        if arg.eq_ignore_ascii_case(b"NOMKSTREAM") {
+            i += 1;
+        } else if arg.eq_ignore_ascii_case(b"MAXLEN") || arg.eq_ignore_ascii_case(b"MINID") {
+            i += 1;
+            if matches!(args.get(i).map(Vec::as_slice), Some(b"=" | b"~")) {
+                i += 1;
+            }
+            i += 1;
+            if args
+                .get(i)
+                .is_some_and(|arg| arg.eq_ignore_ascii_case(b"LIMIT"))
+            {
+                i += 2;
+            }
+        } else {
+            break;
+        }
+    }
+    let id = args.get(i)?;
+    let fields = args.get(i + 1..)?;
+    if fields.is_empty() || !fields.len().is_multiple_of(2) {
+        return None;
+    }
+    let id = (id.as_slice() != b"*").then(|| String::from_utf8_lossy(id).into_owned());
+    let fields = fields
+        .chunks(2)
+        .map(|field| {
+            let value = String::from_utf8_lossy(&field[1]).into_owned();
+            (
+                String::from_utf8_lossy(&field[0]).into_owned(),
+                value.into(),
+            )
+        })
+        .collect();
+    Some((id, fields))
+}
+
+fn stream_id(offset: Option<Offset>) -> String {
+    match offset {
+        Some(offset) => format!("{}-{}", offset.partition, offset.offset),
+        None => format!("{}-0", chrono::Utc::now().timestamp_millis()),
+    }
+}
+
+impl Context {
+    fn record(&self, name: &[u8], payload: Vec<u8>) -> Record {
+        if self.key_by_name {
+            return Record {
+                topic: None,
+                key: Some(name.to_vec()),
+                payload,
+            };
+        }
+        let prefix = self.topic_prefix.as_deref().unwrap_or_default();
+        Record {
+            topic: Some(format!("{prefix}{}", String::from_utf8_lossy(name))),
+            key: None,
+            payload,
+        }
+    }
+
+    fn produce<F>(&self, records: Vec<Record>, subject: Option<&str>, reply: F) -> Pending
+    where
+        F: FnOnce(Vec<Option<Offset>>) -> Reply + Send + 'static,
+    {
+        let context = self.clone();
+        let subject = subject.map(str::to_string);
+        Pending::Produce(tokio::spawn(async move {
+            let context = &context;
+            let subject = subject.as_deref();
+            let results: Vec<Result<Option<Offset>>> = futures::stream::iter(records)
+                .map(|record| async move {
+                    let mut headers = Vec::new();
+                    if let (Some(header), Some(subject)) = (&context.subject_header, subject) {
+                        headers.push((header.as_str(), subject.as_bytes()));
+                    }
+                    let message = Message {
+                        topic: record.topic.as_deref(),
+                        key: record.key.as_deref(),
+                        headers,
+                        payload: &record.payload,
+                    };
+                    context.sink.send_with_ack(message, context.ack_mode).await
+                })
+                .buffered(context.concurrency_limit.max(1))
+                .collect()
+                .await;
+            match results.into_iter().collect::<Result<Vec<_>>>() {
+                Ok(offsets) => reply(offsets),
+                Err(e) => {
+                    log::warn!("{e}");
+                    Reply::Error(format!("ERR {e}"))
+                }
+            }
+        }))
+    }
+
+    fn execute(
+        &self,
+        name: &[u8],
+        args: &[Vec<u8>],
+        resp3: &mut bool,
+        subject: Option<&str>,
+    ) -> Pending {
+        let name = String::from_utf8_lossy(name).to_ascii_uppercase();
+        match (name.as_str(), args) {
+            ("PING", []) => Pending::Ready(Reply::Simple("PONG")),
+            ("PING" | "ECHO", [message]) => Pending::Ready(Reply::Bulk(message.clone())),
+            ("HELLO", _) => Pending::Ready(hello(args, resp3)),
+            ("CLIENT" | "SELECT", [_, ..]) => Pending::Ready(Reply::Simple("OK")),
+            ("COMMAND", _) => Pending::Ready(Reply::Array(Vec::new())),
+            ("QUIT", _) => Pending::Quit,
+            ("PUBLISH", [channel, message]) => {
+                let records = vec![self.record(channel, message.clone())];
+                self.produce(records, subject, |_| Reply::Integer(1))
+            }
+            ("LPUSH" | "RPUSH", [key, values @ ..]) if !values.is_empty() => {
+                let records: Vec<Record> = values
+                    .iter()
+                    .map(|value| self.record(key, value.clone()))
+                    .collect();
+                let count = records.len() as i64;
+                self.produce(records, subject, move |_| Reply::Integer(count))
+            }
+            ("XADD", [stream, args @ ..]) => match xadd(args) {
+                Some((id, fields)) => {
+                    let payload = Value::Object(fields).to_string().into_bytes();
+                    let records = vec![self.record(stream, payload)];
+                    self.produce(records, subject, move |offsets| {
+                        let id = id.unwrap_or_else(|| stream_id(offsets[0]));
+                        Reply::Bulk(id.into_bytes())
+                    })
+                }
+                None => Pending::Ready(Reply::Error(
+                    "ERR wrong number of arguments for 'xadd' command".to_string(),
+                )),
+            },
+            ("PING" | "ECHO" | "CLIENT" | "SELECT" | "PUBLISH" | "LPUSH" | "RPUSH", _) => {
+                Pending::Ready(Reply::Error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    name.to_ascii_lowercase()
+                )))
+            }
+            _ => Pending::Ready(Reply::Error(format!("ERR unknown command '{name}'"))),
+        }
+    }
+}
+
+async fn connection<S>(
+    stream: S,
+    subject: Option<ClientSubject>,
+    context: Context,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let subject = subject.map(|ClientSubject(subject)| subject);
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (pending_snd, mut pending_rcv) =
+        mpsc::channel::<(Pending, bool)>(context.concurrency_limit.max(1));
+    let replies = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        while let Some((pending, resp3)) = pending_rcv.recv().await {
+            let quit = matches!(pending, Pending::Quit);
+            let reply = match pending {
+                Pending::Ready(reply) => reply,
+                Pending::Produce(send) => send
+                    .await
+                    .unwrap_or_else(|e| Reply::Error(format!("ERR {e}"))),
+                Pending::Quit => Reply::Simple("OK"),
+            };
+            buf.clear();
+            reply.encode(resp3, &mut buf);
+            writer.write_all(&buf).await?;
+            if quit {
+                break;
+            }
+        }
+        anyhow::Ok(())
+    });
+
+    let mut buf = Vec::new();
+    let mut resp3 = false;
+    let result = loop {
+        let command = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break Ok(()),
+            command = async {
+                backpressure(&context.sink).await;
+                read_command(&mut reader, &mut buf, context.max_message_size).await
+            } => command,
+        };
+        let command = match command {
+            Ok(Some(command)) => command,
+            Ok(None) => break Ok(()),
+            Err(e) => {
+                let reply = Reply::Error(format!("ERR Protocol error: {e}"));
+                let _ = pending_snd.send((Pending::Ready(reply), resp3)).await;
+                break Err(e);
+            }
+        };
+        let Some((name, args)) = command.split_first() else {
+            continue;
+        };
+        let pending = context.execute(name, args, &mut resp3, subject.as_deref());
+        let quit = matches!(pending, Pending::Quit);
+        if pending_snd.send((pending, resp3)).await.is_err() || quit {
+            break Ok(());
+        }
+    };
+    drop(pending_snd);
+    replies.await??;
+    result
+}
+
+#[async_trait]
+impl Server for RespServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let context = Context {
+            sink,
+            concurrency_limit: self.concurrency_limit,
+            max_message_size: self.max_message_size,
+            ack_mode: self.ack_mode,
+            topic_prefix: self.topic_prefix.clone(),
+            key_by_name: self.key_by_name,
+            subject_header: self.tls.tls_subject_header.clone(),
+        };
+        let acceptor = TlsAcceptor::new(&self.tls)?;
+        let listener = TcpListener::bind(&self.address).await?;
+        serve_streams(
+            listener,
+            acceptor,
+            shutdown_trigger_receiver,
+            move |stream, subject, shutdown_trigger_receiver| {
+                connection(stream, subject, context.clone(), shutdown_trigger_receiver)
+            },
+        )
+        .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reply, parse, xadd};
+    use crate::cli::{AckMode, RespServer, Tls};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry};
+    use serde_json::{Value, json};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_parse() {
+        let command = b"*3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n$5\r\nhello\r\n";
+        for len in 0..command.len() {
+            assert_eq!(parse(&command[..len]).unwrap(), None);
+        }
+        let mut pipelined = command.to_vec();
+        pipelined.extend_from_slice(b"PING\r\n");
+        let (parsed, len) = parse(&pipelined).unwrap().unwrap();
+        assert_eq!(parsed, [&b"PUBLISH"[..], b"news", b"hello"]);
+        assert_eq!(len, command.len());
+        assert_eq!(
+            parse(&pipelined[len..]).unwrap(),
+            Some((vec![b"PING".to_vec()], 6))
+        );
+        assert!(parse(b"*x\r\n").is_err());
+        assert!(parse(b"*1\r\n:1\r\n").is_err());
+        assert!(parse(b"*1\r\n$1\r\nab\r\n").is_err());
+    }
+
+    #[test]
+    fn test_xadd() {
+        let args =
+            ["MAXLEN", "~", "1000", "*", "field", "value"].map(|arg| arg.as_bytes().to_vec());
+        let (id, fields) = xadd(&args).unwrap();
+        assert_eq!(id, None);
+        assert_eq!(Value::Object(fields), json!({"field": "value"}));
+        let args = ["1-1", "field"].map(|arg| arg.as_bytes().to_vec());
+        assert_eq!(xadd(&args), None);
+    }
+
+    #[test]
+    fn test_encode() {
+        let reply = Reply::Map(vec![(Reply::bulk("proto"), Reply::Integer(3))]);
+        let mut resp2 = Vec::new();
+        reply.encode(false, &mut resp2);
+        assert_eq!(resp2, b"*2\r\n$5\r\nproto\r\n:3\r\n");
+        let mut resp3 = Vec::new();
+        reply.encode(true, &mut resp3);
+        assert_eq!(resp3, b"%1\r\n$5\r\nproto\r\n:3\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_resp() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(RespServer {
+            address,
+            concurrency_limit: 16,
+            max_message_size: 1024 * 1024,
+            ack_mode: AckMode::Delivered,
+            topic_prefix: Some("redis.".to_string()),
+            key_by_name: false,
+            tls: Tls::default(),
+        });
+
+        let mut stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+        stream
+            .write_all(
+                b"PING\r\n\
+                *3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n$7\r\nmessage\r\n\
+                *4\r\n$5\r\nRPUSH\r\n$4\r\njobs\r\n$1\r\na\r\n$1\r\nb\r\n\
+                FOO\r\n\
+                *5\r\n$4\r\nXADD\r\n$6\r\nevents\r\n$1\r\n*\r\n$5\r\nfield\r\n$5\r\nvalue\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut replies = Vec::new();
+        while replies.windows(2).filter(|w| w == b"\r\n").count() < 6 {
+            let len = stream.read_buf(&mut replies).await.unwrap();
+            assert_ne!(len, 0);
+        }
+        let replies = String::from_utf8(replies).unwrap();
+        assert!(
+            replies.starts_with("+PONG\r\n:1\r\n:2\r\n-ERR unknown command 'FOO'\r\n$3\r\n0-"),
+            "{replies}"
+        );
+
+        let mut messages = server.sink.messages().await;
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec![
+                b"a".to_vec(),
+                b"b".to_vec(),
+                b"message".to_vec(),
+                br#"{"field":"value"}"#.to_vec(),
+            ]
+        );
+        let mut topics = server.sink.topics().await;
+        topics.sort();
+        assert_eq!(
+            topics,
+            ["redis.events", "redis.jobs", "redis.jobs", "redis.news"].map(|t| Some(t.to_string()))
+        );
+
+        server.stop().await;
+    }
+}
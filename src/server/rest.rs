@@ -14,30 +14,127 @@
  * limitations under the License.
  */
 
-use std::sync::Arc;
-
 use anyhow::Result;
 use async_trait::async_trait;
-use axum::Router;
 use axum::body::Bytes;
-use axum::extract::State;
+use axum::extract::ws::{WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Query, Request, State};
 use axum::http::StatusCode;
+use axum::http::header::RETRY_AFTER;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
+use axum::{Extension, Router};
+use futures::{SinkExt, StreamExt};
 use rdkafka::message::ToBytes;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
 
-use crate::Server;
-use crate::cli::RestServer;
-use crate::kafka::KafkaProducer;
+use crate::cli::{AckMode, RestServer};
+use crate::server::auth::{Authenticator, Credentials, Principal};
+use crate::server::rate_limit::RateLimiter;
+use crate::server::{Message, Offset, Server, SharedSink, backpressure, reply};
+use crate::tls::{ClientSubject, TlsAcceptor};
+
+#[derive(Clone)]
+struct RestState {
+    sink: SharedSink,
+    subject_header: Option<String>,
+    ack_mode: AckMode,
+    authenticator: Option<Arc<Authenticator>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    closing: broadcast::Sender<()>,
+}
+
+const MAX_PENDING_FRAMES: usize = 1024;
+
+#[derive(Deserialize)]
+struct WebSocketParams {
+    #[serde(default)]
+    ack: bool,
+}
+
+struct WebSocketConnection {
+    state: RestState,
+    peer: SocketAddr,
+    principal: Option<String>,
+    subject: Option<String>,
+    ack: bool,
+}
+
+async fn auth_middleware(
+    State(state): State<RestState>,
+    subject: Option<Extension<ClientSubject>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(authenticator) = &state.authenticator else {
+        return next.run(request).await;
+    };
+    let credentials = Credentials::from_headers(request.headers(), subject.as_deref());
+    match authenticator.check(credentials, state.sink.topic()).await {
+        Ok(principal) => {
+            request.extensions_mut().insert(Principal(principal));
+            next.run(request).await
+        }
+        Err(rejection) => {
+            state.sink.rejected(rejection.reason());
+            rejection.into_response()
+        }
+    }
+}
+
+async fn rate_limit_middleware(
+    State(state): State<RestState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    principal: Option<Extension<Principal>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(rate_limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+    let principal = principal.as_ref().map(|Extension(Principal(p))| p.as_str());
+    match rate_limiter.check(Some(peer.ip()), principal) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            state.sink.rejected("rate_limited");
+            let retry_after = retry_after.as_secs_f64().ceil().to_string();
+            (StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, retry_after)]).into_response()
+        }
+    }
+}
 
 async fn produce_handler(
-    State(kafka_producer): State<Arc<KafkaProducer>>,
+    State(state): State<RestState>,
+    subject: Option<Extension<ClientSubject>>,
     bytes: Bytes,
 ) -> std::result::Result<StatusCode, StatusCode> {
-    match kafka_producer.send(bytes.to_bytes()).await {
-        Ok(()) => Ok(StatusCode::NO_CONTENT),
+    if state.sink.saturated() {
+        state.sink.rejected("backpressure");
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let mut headers = Vec::new();
+    if let (Some(header), Some(Extension(ClientSubject(subject)))) =
+        (&state.subject_header, &subject)
+    {
+        headers.push((header.as_str(), subject.as_bytes()));
+    }
+    let message = Message {
+        topic: None,
+        key: None,
+        headers,
+        payload: bytes.to_bytes(),
+    };
+    match state.sink.send_with_ack(message, state.ack_mode).await {
+        Ok(_) if state.ack_mode == AckMode::Delivered => Ok(StatusCode::NO_CONTENT),
+        Ok(_) => Ok(StatusCode::ACCEPTED),
         Err(e) => {
             log::warn!("{e}");
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -45,23 +142,377 @@ async fn produce_handler(
     }
 }
 
+async fn websocket_handler(
+    State(state): State<RestState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    principal: Option<Extension<Principal>>,
+    subject: Option<Extension<ClientSubject>>,
+    Query(params): Query<WebSocketParams>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let closing = state.closing.subscribe();
+    let connection = WebSocketConnection {
+        state,
+        peer,
+        principal: principal.map(|Extension(Principal(principal))| principal),
+        subject: subject.map(|Extension(ClientSubject(subject))| subject),
+        ack: params.ack,
+    };
+    upgrade.on_upgrade(move |socket| websocket(socket, connection, closing))
+}
+
+async fn websocket(socket: WebSocket, connection: WebSocketConnection, mut closing: Receiver<()>) {
+    let (mut writer, mut reader) = socket.split();
+    let (pending_snd, mut pending_rcv) =
+        mpsc::channel::<JoinHandle<Result<Option<Offset>>>>(MAX_PENDING_FRAMES);
+    let ack = connection.ack;
+    let replies = tokio::spawn(async move {
+        while let Some(pending) = pending_rcv.recv().await {
+            let result = pending.await.unwrap_or_else(|e| Err(e.into()));
+            if !ack {
+                continue;
+            }
+            if let Err(e) = writer.send(reply(&result).into()).await {
+                log::warn!("Failed to reply: {e}");
+                break;
+            }
+        }
+        let _ = writer.close().await;
+    });
+
+    let WebSocketConnection {
+        state,
+        peer,
+        principal,
+        subject,
+        ..
+    } = connection;
+    loop {
+        let frame = tokio::select! {
+            _ = closing.recv() => break,
+            frame = async {
+                backpressure(&state.sink).await;
+                reader.next().await
+            } => match frame {
+                Some(Ok(frame)) => frame,
+                _ => break,
+            },
+        };
+        let payload = match frame {
+            axum::extract::ws::Message::Text(text) => Bytes::from(text),
+            axum::extract::ws::Message::Binary(bytes) => bytes,
+            axum::extract::ws::Message::Close(_) => break,
+            _ => continue,
+        };
+        if let Some(rate_limiter) = &state.rate_limiter {
+            rate_limiter
+                .wait(Some(peer.ip()), principal.as_deref())
+                .await;
+        }
+        let state = state.clone();
+        let subject = subject.clone();
+        let pending = tokio::spawn(async move {
+            let mut headers = Vec::new();
+            if let (Some(header), Some(subject)) = (&state.subject_header, &subject) {
+                headers.push((header.as_str(), subject.as_bytes()));
+            }
+            let message = Message {
+                topic: None,
+                key: None,
+                headers,
+                payload: &payload,
+            };
+            let result = state.sink.send_with_ack(message, state.ack_mode).await;
+            if let Err(e) = &result {
+                log::warn!("{e}");
+            }
+            result
+        });
+        if pending_snd.send(pending).await.is_err() {
+            break;
+        }
+    }
+    drop(pending_snd);
+    let _ = replies.await;
+}
+
 #[async_trait]
 impl Server for RestServer {
     async fn run(
         &self,
-        kafka_producer: KafkaProducer,
+        sink: SharedSink,
         mut shutdown_trigger_receiver: Receiver<()>,
         _shutdown_sender: Sender<()>,
     ) -> Result<()> {
+        let state = RestState {
+            sink,
+            subject_header: self.tls.tls_subject_header.clone(),
+            ack_mode: self.ack_mode,
+            authenticator: Authenticator::new(&self.auth, self.tls.tls_client_ca.is_some())?,
+            rate_limiter: RateLimiter::with_principals(&self.rate_limit),
+            closing: broadcast::channel(1).0,
+        };
+        let closing = state.closing.clone();
         let app = Router::new()
-            .route("/produce", post(produce_handler))
-            .with_state(Arc::new(kafka_producer));
+            .route("/produce", post(produce_handler).get(websocket_handler))
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                rate_limit_middleware,
+            ))
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ))
+            .with_state(state);
+        let tls = TlsAcceptor::http(&self.tls)?;
         let listener = TcpListener::bind(&self.address).await?;
-        axum::serve(listener, app)
-            .with_graceful_shutdown(async move {
-                let _ = shutdown_trigger_receiver.recv().await;
-            })
-            .await?;
+        crate::tls::serve(listener, tls, app, async move {
+            let _ = shutdown_trigger_receiver.recv().await;
+            let _ = closing.send(());
+        })
+        .await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{AckMode, Auth, PrincipalRateLimit, RateLimit, RestServer, Tls};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry, temp_path};
+    use futures::{SinkExt, StreamExt};
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair};
+    use reqwest::StatusCode;
+    use tokio_tungstenite::connect_async;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    #[tokio::test]
+    async fn test_produce() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            tls: Tls::default(),
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit::default(),
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{address}/produce");
+        let response =
+            retry(|| async { Ok(client.post(&url).body("message").send().await?) }).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(server.sink.messages().await, vec![b"message".to_vec()]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_ack_mode() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Enqueued,
+            tls: Tls::default(),
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit::default(),
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{address}/produce");
+        let response =
+            retry(|| async { Ok(client.post(&url).body("message").send().await?) }).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(server.sink.messages().await, vec![b"message".to_vec()]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_auth() {
+        let api_keys = temp_path("rest-api-keys");
+        std::fs::write(&api_keys, "service:secret-key\n").unwrap();
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            tls: Tls::default(),
+            auth: Auth {
+                api_keys: Some(api_keys),
+                ..Auth::default()
+            },
+            rate_limit: PrincipalRateLimit::default(),
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{address}/produce");
+        let response =
+            retry(|| async { Ok(client.post(&url).body("anonymous").send().await?) }).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = client
+            .post(&url)
+            .header("X-Api-Key", "secret-key")
+            .body("message")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(server.sink.messages().await, vec![b"message".to_vec()]);
+        assert_eq!(server.sink.rejected_reasons(), vec!["unauthenticated"]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            tls: Tls::default(),
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit {
+                rate_limit: RateLimit {
+                    rate_limit_per_ip: Some(0.5),
+                    ..RateLimit::default()
+                },
+                ..PrincipalRateLimit::default()
+            },
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{address}/produce");
+        let response =
+            retry(|| async { Ok(client.post(&url).body("message").send().await?) }).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = client.post(&url).body("message").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "2");
+        assert_eq!(server.sink.rejected_reasons(), vec!["rate_limited"]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            tls: Tls::default(),
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit::default(),
+        });
+        server.sink.set_saturated(true);
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{address}/produce");
+        let response =
+            retry(|| async { Ok(client.post(&url).body("message").send().await?) }).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        server.sink.set_saturated(false);
+        let response = client.post(&url).body("message").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(server.sink.messages().await, vec![b"message".to_vec()]);
+        assert_eq!(server.sink.rejected_reasons(), vec!["backpressure"]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_websocket() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            tls: Tls::default(),
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit::default(),
+        });
+
+        let url = format!("ws://{address}/produce?ack=true");
+        let (mut socket, _) = retry(|| async { Ok(connect_async(&url).await?) }).await;
+        socket.send(WsMessage::text("first")).await.unwrap();
+        socket
+            .send(WsMessage::binary(b"second".to_vec()))
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let reply = socket.next().await.unwrap().unwrap();
+            assert!(reply.to_text().unwrap().starts_with("OK 0:"));
+        }
+        socket.close(None).await.unwrap();
+        let mut messages = server.sink.messages().await;
+        messages.sort();
+        assert_eq!(messages, vec![b"first".to_vec(), b"second".to_vec()]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.distinguished_name.push(DnType::CommonName, "ca");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let issuer = Issuer::from_params(&ca_params, &ca_key);
+
+        let server_key = KeyPair::generate().unwrap();
+        let server_cert = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &issuer)
+            .unwrap();
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::new()).unwrap();
+        client_params
+            .distinguished_name
+            .push(DnType::CommonName, "client");
+        let client_cert = client_params.signed_by(&client_key, &issuer).unwrap();
+
+        let tls = Tls {
+            tls_cert: Some(temp_path("server.pem")),
+            tls_key: Some(temp_path("server.key")),
+            tls_client_ca: Some(temp_path("ca.pem")),
+            tls_subject_header: Some("subject".to_string()),
+        };
+        std::fs::write(tls.tls_cert.as_ref().unwrap(), server_cert.pem()).unwrap();
+        std::fs::write(tls.tls_key.as_ref().unwrap(), server_key.serialize_pem()).unwrap();
+        std::fs::write(tls.tls_client_ca.as_ref().unwrap(), ca.pem()).unwrap();
+
+        let address = free_tcp_address();
+        let server = RunningServer::start(RestServer {
+            address,
+            ack_mode: AckMode::Delivered,
+            tls,
+            auth: Auth::default(),
+            rate_limit: PrincipalRateLimit::default(),
+        });
+
+        let ca = reqwest::Certificate::from_pem(ca.pem().as_bytes()).unwrap();
+        let identity = format!("{}{}", client_cert.pem(), client_key.serialize_pem());
+        let client = reqwest::Client::builder()
+            .tls_backend_rustls()
+            .tls_certs_only([ca.clone()])
+            .identity(reqwest::Identity::from_pem(identity.as_bytes()).unwrap())
+            .build()
+            .unwrap();
+        let url = format!("https://localhost:{}/produce", address.port());
+        let response =
+            retry(|| async { Ok(client.post(&url).body("message").send().await?) }).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(server.sink.messages().await, vec![b"message".to_vec()]);
+        assert_eq!(
+            server.sink.headers().await,
+            vec![vec![("subject".to_string(), b"CN=client".to_vec())]]
+        );
+
+        let anonymous = reqwest::Client::builder()
+            .tls_backend_rustls()
+            .tls_certs_only([ca])
+            .build()
+            .unwrap();
+        assert!(anonymous.post(&url).body("message").send().await.is_err());
+
+        server.stop().await;
+    }
+}
@@ -16,10 +16,13 @@
 
 use crate::cli::UdpSocketServer;
 use crate::cli::UnixDatagramServer;
+use crate::server::SharedSink;
+use crate::server::rate_limit::RateLimiter;
 use crate::server::stream::cleanup::ListenerCleanup;
-use crate::server::stream::{BytesStream, MessageStream};
+use crate::server::stream::{BytesStream, MessageStream, PeerAddr};
 use anyhow::Result;
 use async_trait::async_trait;
+use std::sync::Arc;
 use tokio::net::UdpSocket;
 use tokio::net::UnixDatagram;
 use tokio::sync::broadcast::Receiver;
@@ -27,25 +30,42 @@ use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 
 macro_rules! datagram_socket_message_stream {
-    ($tp:ty, $self:ident => $socket:expr) => {
+    ($tp:ty, $self:ident => $socket:expr, $rate_limiter:expr, $dead_letter:expr) => {
         #[async_trait]
         impl MessageStream for $tp {
             fn concurrency_limit(&self) -> usize {
                 self.concurrency_limit
             }
 
-            async fn stream(&$self, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
+            async fn stream(&$self, sink: SharedSink, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
                 let socket = $socket;
+                let rate_limiter: Option<Arc<RateLimiter>> = $rate_limiter;
+                let dead_letter = $dead_letter;
                 let mut buf = [0; 8192];
                 let (snd, rcv) = mpsc::channel(1);
                 tokio::spawn(async move {
                     loop {
-                        let len = tokio::select! {
+                        let received = tokio::select! {
                             _ = shutdown_trigger_receiver.recv() => break,
-                            len = socket.recv(&mut buf) => len,
+                            received = socket.recv_from(&mut buf) => received,
                         };
-                        let msg = match len {
-                            Ok(len) => Ok(buf[..len].into()),
+                        let msg = match received {
+                            Ok((_, _)) if sink.saturated() => {
+                                sink.rejected("backpressure");
+                                continue;
+                            }
+                            Ok((len, peer)) => {
+                                if let Some(rate_limiter) = &rate_limiter
+                                    && rate_limiter.check(peer.peer_ip(), None).is_err()
+                                {
+                                    sink.rejected("rate_limited");
+                                    if dead_letter && let Err(e) = sink.dead_letter(&buf[..len]).await {
+                                        log::warn!("Failed to write dead letter: {e}");
+                                    }
+                                    continue;
+                                }
+                                Ok(buf[..len].into())
+                            }
                             Err(e) => Err(e.into()),
                         };
                         if let Err(e) = snd.send(msg).await {
@@ -60,6 +80,66 @@ macro_rules! datagram_socket_message_stream {
     };
 }
 
-datagram_socket_message_stream!(UdpSocketServer, self => UdpSocket::bind(self.address).await?);
+datagram_socket_message_stream!(
+    UdpSocketServer,
+    self => UdpSocket::bind(self.address).await?,
+    RateLimiter::new(&self.rate_limit),
+    self.rate_limit_dead_letter
+);
 
-datagram_socket_message_stream!(UnixDatagramServer, self => ListenerCleanup::<UnixDatagram>::bind(self.path.clone())?);
+datagram_socket_message_stream!(
+    UnixDatagramServer,
+    self => ListenerCleanup::<UnixDatagram>::bind(self.path.clone())?,
+    None,
+    false
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{RateLimit, UdpSocketServer, UnixDatagramServer};
+    use crate::server::tests::{RunningServer, free_udp_address, retry, temp_path};
+    use anyhow::bail;
+    use std::time::Duration;
+    use tokio::net::{UdpSocket, UnixDatagram};
+
+    #[tokio::test]
+    async fn test_udp() {
+        let address = free_udp_address();
+        let server = RunningServer::start(UdpSocketServer {
+            concurrency_limit: 1,
+            address,
+            rate_limit: RateLimit::default(),
+            rate_limit_dead_letter: false,
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let messages = retry(|| async {
+            socket.send_to(b"message", address).await?;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            match server.sink.messages().await {
+                messages if messages.is_empty() => bail!("No message received"),
+                messages => Ok(messages),
+            }
+        })
+        .await;
+        assert_eq!(messages[0], b"message".to_vec());
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_unix_datagram() {
+        let path = temp_path("unix-dgram.sock");
+        let server = RunningServer::start(UnixDatagramServer {
+            concurrency_limit: 1,
+            path: path.clone(),
+        });
+
+        let socket = UnixDatagram::unbound().unwrap();
+        retry(|| async { Ok(socket.send_to(b"message", &path).await?) }).await;
+        assert_eq!(server.sink.wait_for(1).await, vec![b"message".to_vec()]);
+
+        server.stop().await;
+        assert!(!path.exists());
+    }
+}
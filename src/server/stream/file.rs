@@ -15,6 +15,7 @@
  */
 
 use crate::cli::{FileServer, StdInServer};
+use crate::server::SharedSink;
 use crate::server::decoder::decode_line;
 use crate::server::stream::{BytesStream, MessageStream};
 use anyhow::Result;
@@ -33,7 +34,7 @@ macro_rules! buf_reader_message_stream {
                self.concurrency_limit
             }
 
-            async fn stream(&$self, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
+            async fn stream(&$self, _sink: SharedSink, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
                 let reader = BufReader::new($reader);
                 let mut lines = reader.lines();
                 let base64 = $self.base64;
@@ -73,3 +74,28 @@ macro_rules! buf_reader_message_stream {
 buf_reader_message_stream!(StdInServer, self => tokio::io::stdin());
 
 buf_reader_message_stream!(FileServer, self => File::open(&self.file).await?);
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::FileServer;
+    use crate::server::tests::{RunningServer, temp_path};
+
+    #[tokio::test]
+    async fn test_file() {
+        let file = temp_path("file.txt");
+        std::fs::write(&file, "first\nsecond\n").unwrap();
+        let server = RunningServer::start(FileServer {
+            concurrency_limit: 1,
+            base64: false,
+            file: file.clone(),
+        });
+
+        assert_eq!(
+            server.sink.wait_for(2).await,
+            vec![b"first".to_vec(), b"second".to_vec()]
+        );
+
+        server.stop().await;
+        std::fs::remove_file(file).unwrap();
+    }
+}
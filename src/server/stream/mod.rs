@@ -23,22 +23,47 @@ mod nng;
 mod posixmq;
 mod socket;
 
-use crate::kafka::KafkaProducer;
-use crate::server::Server;
+use crate::cli::AckMode;
+use crate::server::{Message, Server, SharedSink};
 use anyhow::Result;
 use async_trait::async_trait;
 use futures::stream::StreamExt;
+use std::net::{IpAddr, SocketAddr};
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::mpsc::Sender;
 use tokio_stream::Stream;
 
 type BytesStream = Box<dyn Stream<Item = Result<Vec<u8>>> + Send + Unpin>;
 
+trait PeerAddr {
+    fn peer_ip(&self) -> Option<IpAddr>;
+}
+
+impl PeerAddr for SocketAddr {
+    fn peer_ip(&self) -> Option<IpAddr> {
+        Some(self.ip())
+    }
+}
+
+impl PeerAddr for tokio::net::unix::SocketAddr {
+    fn peer_ip(&self) -> Option<IpAddr> {
+        None
+    }
+}
+
 #[async_trait]
 trait MessageStream {
     fn concurrency_limit(&self) -> usize;
 
-    async fn stream(&self, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream>;
+    fn ack_mode(&self) -> AckMode {
+        AckMode::Delivered
+    }
+
+    async fn stream(
+        &self,
+        sink: SharedSink,
+        mut shutdown_trigger_receiver: Receiver<()>,
+    ) -> Result<BytesStream>;
 }
 
 #[async_trait]
@@ -48,11 +73,11 @@ where
 {
     async fn run(
         &self,
-        kafka_producer: KafkaProducer,
+        sink: SharedSink,
         mut shutdown_trigger_receiver: Receiver<()>,
         _shutdown_sender: Sender<()>,
     ) -> Result<()> {
-        let stream = self.stream(shutdown_trigger_receiver.resubscribe());
+        let stream = self.stream(sink.clone(), shutdown_trigger_receiver.resubscribe());
         let messages = tokio::select! {
             _ = shutdown_trigger_receiver.recv() => return Ok(()),
             messages = stream => messages?,
@@ -61,8 +86,11 @@ where
             .for_each_concurrent(self.concurrency_limit(), |msg| async {
                 match msg {
                     Err(e) => log::error!("{e}"),
-                    Ok(msg) => match kafka_producer.send(&msg).await {
-                        Ok(()) => (),
+                    Ok(msg) => match sink
+                        .send_with_ack(Message::from(msg.as_slice()), self.ack_mode())
+                        .await
+                    {
+                        Ok(_) => (),
                         Err(e) => log::warn!("{e}"),
                     },
                 };
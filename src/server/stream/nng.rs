@@ -14,8 +14,9 @@
  * limitations under the License.
  */
 
-use crate::cli::NngServer;
+use crate::cli::{AckMode, NngServer};
 use crate::server::stream::{BytesStream, MessageStream};
+use crate::server::{SharedSink, reply};
 use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -29,7 +30,7 @@ use tokio::sync::mpsc::Sender;
 use tokio_stream::wrappers::ReceiverStream;
 
 async fn nng_loop(
-    ack: bool,
+    ack: Option<(SharedSink, AckMode)>,
     socket: Socket,
     snd: Sender<Result<Vec<u8>>>,
     mut shutdown_trigger_receiver: Receiver<()>,
@@ -42,15 +43,21 @@ async fn nng_loop(
             _ = shutdown_trigger_receiver.recv() => break,
             msg = recv => msg??,
         };
-        snd.send(Ok(msg.to_vec())).await?;
+        let Some((sink, ack_mode)) = &ack else {
+            snd.send(Ok(msg.to_vec())).await?;
+            continue;
+        };
 
-        if ack {
-            let sock = socket.clone();
-            let reply = tokio::task::spawn_blocking(move || sock.send(Message::new()));
-            tokio::select! {
-                _ = shutdown_trigger_receiver.recv() => break,
-                reply = reply => reply?.map_err(|(_, e)| e)?,
-            }
+        let result = sink.send_with_ack(msg.as_slice().into(), *ack_mode).await;
+        if let Err(e) = &result {
+            log::warn!("{e}");
+        }
+        let reply = Message::from(reply(&result).as_bytes());
+        let sock = socket.clone();
+        let reply = tokio::task::spawn_blocking(move || sock.send(reply));
+        tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            reply = reply => reply?.map_err(|(_, e)| e)?,
         }
     }
     Ok(())
@@ -62,20 +69,65 @@ impl MessageStream for NngServer {
         self.concurrency_limit
     }
 
-    async fn stream(&self, shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
+    fn ack_mode(&self) -> AckMode {
+        self.ack_mode
+    }
+
+    async fn stream(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+    ) -> Result<BytesStream> {
         let (snd, rcv) = mpsc::channel(1);
         let acknowledge =
             self.acknowledge && self.protocol != Protocol::Pull0 && self.protocol != Protocol::Sub0;
+        let ack = acknowledge.then_some((sink, self.ack_mode));
         let socket = Socket::new(self.protocol)?;
         if self.protocol == Protocol::Sub0 {
             socket.set_opt::<Subscribe>(vec![])?;
         }
         socket.listen(&self.address).context("Invalid address")?;
+        let closing = socket.clone();
         tokio::spawn(async move {
-            if let Err(e) = nng_loop(acknowledge, socket, snd, shutdown_trigger_receiver).await {
+            if let Err(e) = nng_loop(ack, socket, snd, shutdown_trigger_receiver).await {
                 log::error!("{e}");
             }
+            // Unblocks the pending receive
+            closing.close();
         });
         Ok(Box::new(ReceiverStream::new(rcv)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{AckMode, NngServer};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry};
+    use nng::{Protocol, Socket};
+
+    #[tokio::test]
+    async fn test_acknowledge() {
+        let address = format!("tcp://{}", free_tcp_address());
+        let server = RunningServer::start(NngServer {
+            concurrency_limit: 1,
+            protocol: Protocol::Rep0,
+            address: address.clone(),
+            acknowledge: true,
+            ack_mode: AckMode::Delivered,
+        });
+
+        let socket = Socket::new(Protocol::Req0).unwrap();
+        retry(|| async { Ok(socket.dial(&address)?) }).await;
+        let reply = tokio::task::spawn_blocking(move || {
+            socket.send(b"message".as_slice()).map_err(|(_, e)| e)?;
+            socket.recv()
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(reply.as_slice(), b"OK 0:0");
+        assert_eq!(server.sink.messages().await, vec![b"message".to_vec()]);
+
+        server.stop().await;
+    }
+}
@@ -25,6 +25,7 @@ use tokio::sync::mpsc::Sender;
 use tokio_stream::wrappers::ReceiverStream;
 
 use crate::cli::PosixMQServer;
+use crate::server::SharedSink;
 use crate::server::stream::{BytesStream, MessageStream};
 
 async fn mq_loop(
@@ -54,7 +55,11 @@ impl MessageStream for PosixMQServer {
         self.concurrency_limit
     }
 
-    async fn stream(&self, shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
+    async fn stream(
+        &self,
+        _sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+    ) -> Result<BytesStream> {
         let (snd, rcv) = mpsc::channel(1);
 
         let name = self.name.clone();
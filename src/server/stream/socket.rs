@@ -14,47 +14,157 @@
  * limitations under the License.
  */
 
-use crate::cli::{TcpSocketServer, UnixSocketServer};
+use crate::cli::{AckMode, TcpSocketServer, UnixSocketServer};
 use crate::server::decoder::decode_line;
+use crate::server::rate_limit::RateLimiter;
 use crate::server::stream::cleanup::ListenerCleanup;
-use crate::server::stream::{BytesStream, MessageStream};
+use crate::server::stream::{BytesStream, MessageStream, PeerAddr};
+use crate::server::{Message, Offset, SharedSink, backpressure, reply};
 use anyhow::Result;
 use async_trait::async_trait;
-use tokio::io::{AsyncBufReadExt, BufStream};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufStream};
 use tokio::net::{TcpListener, UnixListener};
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
 use tokio_stream::wrappers::ReceiverStream;
 
+type Pending = Result<JoinHandle<Result<Option<Offset>>>>;
+
+struct Connection {
+    sink: SharedSink,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    peer_ip: Option<IpAddr>,
+    base64: bool,
+    concurrency_limit: usize,
+    ack_mode: AckMode,
+}
+
+async fn acknowledged<S>(
+    stream: S,
+    connection: Connection,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (pending_snd, mut pending_rcv) =
+        mpsc::channel::<Pending>(connection.concurrency_limit.max(1));
+    let replies = tokio::spawn(async move {
+        while let Some(pending) = pending_rcv.recv().await {
+            let result = match pending {
+                Ok(send) => send.await.unwrap_or_else(|e| Err(e.into())),
+                Err(e) => Err(e),
+            };
+            let mut line = reply(&result);
+            line.push('\n');
+            if let Err(e) = writer.write_all(line.as_bytes()).await {
+                log::warn!("Failed to reply: {e}");
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let line = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            line = async {
+                backpressure(&connection.sink).await;
+                lines.next_line().await
+            } => match line {
+                Ok(Some(line)) => line,
+                _ => break,
+            },
+        };
+        let pending = match decode_line(line, connection.base64) {
+            Ok(payload) => {
+                if let Some(rate_limiter) = &connection.rate_limiter {
+                    rate_limiter.wait(connection.peer_ip, None).await;
+                }
+                let sink = connection.sink.clone();
+                let ack_mode = connection.ack_mode;
+                Ok(tokio::spawn(async move {
+                    let result = sink
+                        .send_with_ack(Message::from(payload.as_slice()), ack_mode)
+                        .await;
+                    if let Err(e) = &result {
+                        log::warn!("{e}");
+                    }
+                    result
+                }))
+            }
+            Err(e) => {
+                log::warn!("Failed to decode: {e}");
+                Err(e)
+            }
+        };
+        if pending_snd.send(pending).await.is_err() {
+            break;
+        }
+    }
+    drop(pending_snd);
+    let _ = replies.await;
+}
+
 macro_rules! socket_message_stream {
-    ($tp:ty, $self:ident => $listener:expr) => {
+    ($tp:ty, $self:ident => $listener:expr, $rate_limiter:expr) => {
         #[async_trait]
         impl MessageStream for $tp {
             fn concurrency_limit(&self) -> usize {
                self.concurrency_limit
             }
 
-            async fn stream(&$self, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
+            fn ack_mode(&self) -> AckMode {
+                self.ack_mode
+            }
+
+            async fn stream(&$self, sink: SharedSink, mut shutdown_trigger_receiver: Receiver<()>) -> Result<BytesStream> {
                 let listener = $listener;
+                let rate_limiter: Option<Arc<RateLimiter>> = $rate_limiter;
                 let base64 = $self.base64;
+                let acknowledge = $self.acknowledge;
+                let concurrency_limit = $self.concurrency_limit;
+                let ack_mode = $self.ack_mode;
                 let (snd, rcv) = mpsc::channel(1);
                 tokio::spawn(async move {
+                    let mut connections = JoinSet::new();
                     loop {
                         let mut shutdown_trigger_receiver_inner = shutdown_trigger_receiver.resubscribe();
-                        let stream = tokio::select! {
+                        let (stream, peer) = tokio::select! {
                             _ = shutdown_trigger_receiver.recv() => break,
+                            Some(_) = connections.join_next() => continue,
                             res = listener.accept() => match res {
-                                Ok((stream, _)) => stream,
+                                Ok(accepted) => accepted,
                                 Err(_) => break,
                             },
                         };
+                        let rate_limiter = rate_limiter.clone();
+                        let sink = sink.clone();
+                        if acknowledge {
+                            let connection = Connection {
+                                sink,
+                                rate_limiter,
+                                peer_ip: peer.peer_ip(),
+                                base64,
+                                concurrency_limit,
+                                ack_mode,
+                            };
+                            connections.spawn(acknowledged(stream, connection, shutdown_trigger_receiver_inner));
+                            continue;
+                        }
                         let snd = snd.clone();
                         tokio::spawn(async move {
                             let mut lines = BufStream::new(stream).lines();
                             loop {
                                 tokio::select! {
                                     _ = shutdown_trigger_receiver_inner.recv() => break,
-                                    line = lines.next_line() => match line {
+                                    line = async {
+                                        backpressure(&sink).await;
+                                        lines.next_line().await
+                                    } => match line {
                                         Ok(Some(l)) => {
                                             let b = match decode_line(l, base64) {
                                                 Ok(l) => l,
@@ -63,6 +173,9 @@ macro_rules! socket_message_stream {
                                                     continue
                                                 }
                                             };
+                                            if let Some(rate_limiter) = &rate_limiter {
+                                                rate_limiter.wait(peer.peer_ip(), None).await;
+                                            }
                                             match snd.send(Ok(b)).await {
                                                 Ok(()) => (),
                                                 Err(e) => {
@@ -77,6 +190,8 @@ macro_rules! socket_message_stream {
                             }
                         });
                     }
+                    // Keep the stream open until all acknowledged lines have been replied to
+                    connections.join_all().await;
                 });
                 Ok(Box::new(ReceiverStream::new(rcv)))
             }
@@ -84,6 +199,89 @@ macro_rules! socket_message_stream {
     };
 }
 
-socket_message_stream!(UnixSocketServer, self => ListenerCleanup::<UnixListener>::bind(self.file.clone())?);
+socket_message_stream!(UnixSocketServer, self => ListenerCleanup::<UnixListener>::bind(self.file.clone())?, None);
+
+socket_message_stream!(TcpSocketServer, self => TcpListener::bind(self.address).await?, RateLimiter::new(&self.rate_limit));
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{AckMode, RateLimit, TcpSocketServer, UnixSocketServer};
+    use crate::server::tests::{RunningServer, free_tcp_address, retry, temp_path};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpStream, UnixStream};
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(TcpSocketServer {
+            concurrency_limit: 1,
+            base64: false,
+            acknowledge: false,
+            ack_mode: AckMode::Delivered,
+            address,
+            rate_limit: RateLimit::default(),
+        });
+
+        let mut stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+        stream.write_all(b"first\nsecond\n").await.unwrap();
+        assert_eq!(
+            server.sink.wait_for(2).await,
+            vec![b"first".to_vec(), b"second".to_vec()]
+        );
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(TcpSocketServer {
+            concurrency_limit: 4,
+            base64: true,
+            acknowledge: true,
+            ack_mode: AckMode::Delivered,
+            address,
+            rate_limit: RateLimit::default(),
+        });
 
-socket_message_stream!(TcpSocketServer, self => TcpListener::bind(self.address).await?);
+        let stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+        let (reader, mut writer) = stream.into_split();
+        writer
+            .write_all(b"Zmlyc3Q=\n!invalid!\nc2Vjb25k\n")
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let mut replies = Vec::new();
+        for _ in 0..3 {
+            replies.push(lines.next_line().await.unwrap().unwrap());
+        }
+        assert!(replies[0].starts_with("OK 0:"));
+        assert!(replies[1].starts_with("ERR "));
+        assert!(replies[2].starts_with("OK 0:"));
+        assert_ne!(replies[0], replies[2]);
+        let mut messages = server.sink.messages().await;
+        messages.sort();
+        assert_eq!(messages, vec![b"first".to_vec(), b"second".to_vec()]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_unix() {
+        let file = temp_path("unix.sock");
+        let server = RunningServer::start(UnixSocketServer {
+            concurrency_limit: 1,
+            base64: true,
+            acknowledge: false,
+            ack_mode: AckMode::Delivered,
+            file: file.clone(),
+        });
+
+        let mut stream = retry(|| async { Ok(UnixStream::connect(&file).await?) }).await;
+        stream.write_all(b"bWVzc2FnZQ==\n").await.unwrap();
+        assert_eq!(server.sink.wait_for(1).await, vec![b"message".to_vec()]);
+
+        server.stop().await;
+        assert!(!file.exists());
+    }
+}
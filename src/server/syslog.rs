@@ -0,0 +1,516 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{Map, Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Sender;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::cli::{SyslogServer, Transport};
+use crate::server::{Message, Server, SharedSink, backpressure};
+use crate::tls::{ClientSubject, TlsAcceptor, serve_streams};
+
+const DEFAULT_PRIORITY: u8 = 13;
+const MONTHS: [&[u8]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+#[derive(Debug, Default, PartialEq)]
+struct Syslog<'a> {
+    priority: u8,
+    version: Option<u8>,
+    timestamp: Option<&'a str>,
+    hostname: Option<&'a str>,
+    app: Option<&'a str>,
+    procid: Option<&'a str>,
+    msgid: Option<&'a str>,
+    structured_data: Map<String, Value>,
+    message: Option<&'a str>,
+}
+
+impl Syslog<'_> {
+    fn record(&self) -> Value {
+        json!({
+            "facility": self.priority >> 3,
+            "severity": self.priority & 7,
+            "version": self.version,
+            "timestamp": self.timestamp,
+            "hostname": self.hostname,
+            "app": self.app,
+            "procid": self.procid,
+            "msgid": self.msgid,
+            "structured_data": self.structured_data,
+            "message": self.message,
+        })
+    }
+}
+
+fn parse(line: &str) -> Syslog<'_> {
+    let line = line.trim_end_matches(['\r', '\n', '\0']);
+    let Some((priority, rest)) = priority(line) else {
+        return Syslog {
+            priority: DEFAULT_PRIORITY,
+            message: Some(line),
+            ..Syslog::default()
+        };
+    };
+    match rest.strip_prefix("1 ") {
+        Some(rest) => rfc5424(priority, rest),
+        None => rfc3164(priority, rest),
+    }
+}
+
+fn priority(line: &str) -> Option<(u8, &str)> {
+    let (priority, rest) = line.strip_prefix('<')?.split_once('>')?;
+    if priority.is_empty() || !priority.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let priority = priority.parse().ok().filter(|priority| *priority <= 191)?;
+    Some((priority, rest))
+}
+
+fn rfc5424(priority: u8, rest: &str) -> Syslog<'_> {
+    let mut fields = rest.splitn(6, ' ');
+    let mut field = || {
+        fields
+            .next()
+            .filter(|field| !field.is_empty() && *field != "-")
+    };
+    let timestamp = field();
+    let hostname = field();
+    let app = field();
+    let procid = field();
+    let msgid = field();
+    let rest = fields.next().unwrap_or_default();
+    let (structured_data, message) = structured_data(rest).unwrap_or((Map::new(), rest));
+    let message = message.strip_prefix(' ').unwrap_or(message);
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message);
+    Syslog {
+        priority,
+        version: Some(1),
+        timestamp,
+        hostname,
+        app,
+        procid,
+        msgid,
+        structured_data,
+        message: Some(message).filter(|message| !message.is_empty()),
+    }
+}
+
+fn structured_data(mut rest: &str) -> Option<(Map<String, Value>, &str)> {
+    let mut elements = Map::new();
+    if let Some(rest) = rest.strip_prefix('-') {
+        return Some((elements, rest));
+    }
+    while let Some(element) = rest.strip_prefix('[') {
+        let end = element.find([' ', ']'])?;
+        let id = &element[..end];
+        let mut params = Map::new();
+        rest = &element[end..];
+        while let Some(param) = rest.strip_prefix(' ') {
+            let (name, value) = param.split_once("=\"")?;
+            let mut unescaped = String::new();
+            let mut chars = value.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (_, '\\') => {
+                        let (_, c) = chars.next()?;
+                        if !matches!(c, '"' | '\\' | ']') {
+                            unescaped.push('\\');
+                        }
+                        unescaped.push(c);
+                    }
+                    (i, '"') => break i,
+                    (_, c) => unescaped.push(c),
+                }
+            };
+            params.insert(name.to_string(), unescaped.into());
+            rest = &value[end + 1..];
+        }
+        rest = rest.strip_prefix(']')?;
+        elements.insert(id.to_string(), params.into());
+    }
+    (!elements.is_empty()).then_some((elements, rest))
+}
+
+fn rfc3164(priority: u8, rest: &str) -> Syslog<'_> {
+    let (timestamp, rest) = match bsd_timestamp(rest) {
+        Some((timestamp, rest)) => (Some(timestamp), rest),
+        None => (None, rest),
+    };
+    let (hostname, rest) = match rest.split_once(' ') {
+        Some((hostname, rest))
+            if timestamp.is_some() && !hostname.ends_with(':') && !hostname.contains('[') =>
+        {
+            (Some(hostname), rest)
+        }
+        _ => (None, rest),
+    };
+    let (tag, message) = match rest.split_once(':') {
+        Some((tag, message)) if !tag.is_empty() && !tag.contains(' ') => {
+            (Some(tag), message.strip_prefix(' ').unwrap_or(message))
+        }
+        _ => (None, rest),
+    };
+    let (app, procid) = match tag.and_then(|tag| tag.strip_suffix(']')?.split_once('[')) {
+        Some((app, procid)) => (Some(app), Some(procid)),
+        None => (tag, None),
+    };
+    Syslog {
+        priority,
+        timestamp,
+        hostname,
+        app,
+        procid,
+        message: Some(message).filter(|message| !message.is_empty()),
+        ..Syslog::default()
+    }
+}
+
+fn bsd_timestamp(rest: &str) -> Option<(&str, &str)> {
+    let timestamp = rest.get(..15)?;
+    let b = timestamp.as_bytes();
+    let valid = MONTHS.contains(&&b[..3])
+        && b[3] == b' '
+        && (b[4] == b' ' || b[4].is_ascii_digit())
+        && b[5].is_ascii_digit()
+        && b[6] == b' '
+        && b[9] == b':'
+        && b[12] == b':'
+        && [7, 8, 10, 11, 13, 14]
+            .iter()
+            .all(|i| b[*i].is_ascii_digit());
+    let rest = &rest[15..];
+    valid.then_some((timestamp, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+async fn read_frame<R>(reader: &mut R, max_message_size: usize, frame: &mut Vec<u8>) -> Result<bool>
+where
+    R: AsyncBufRead + Unpin,
+{
+    frame.clear();
+    let octet_counted = match reader.fill_buf().await? {
+        [] => return Ok(false),
+        [first, ..] => first.is_ascii_digit(),
+    };
+    if octet_counted {
+        let mut len = Vec::new();
+        (&mut *reader).take(16).read_until(b' ', &mut len).await?;
+        let len: usize = std::str::from_utf8(&len)?.trim_end().parse()?;
+        if len > max_message_size {
+            bail!("Message exceeds {max_message_size} bytes");
+        }
+        frame.resize(len, 0);
+        reader.read_exact(frame).await?;
+    } else {
+        (&mut *reader)
+            .take(max_message_size as u64)
+            .read_until(b'\n', frame)
+            .await?;
+        if frame.len() >= max_message_size && !frame.ends_with(b"\n") {
+            bail!("Message exceeds {max_message_size} bytes");
+        }
+    }
+    Ok(true)
+}
+
+struct Entry {
+    key: Option<String>,
+    subject: Option<String>,
+    payload: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct Context {
+    sink: SharedSink,
+    entries: Sender<Entry>,
+    key_by_hostname: bool,
+    max_message_size: usize,
+}
+
+impl Context {
+    async fn handle(&self, frame: &[u8], subject: Option<&str>) -> Result<()> {
+        let line = String::from_utf8_lossy(frame);
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let syslog = parse(&line);
+        let entry = Entry {
+            key: syslog
+                .hostname
+                .filter(|_| self.key_by_hostname)
+                .map(str::to_string),
+            subject: subject.map(str::to_string),
+            payload: serde_json::to_vec(&syslog.record())?,
+        };
+        self.entries.send(entry).await?;
+        Ok(())
+    }
+}
+
+async fn udp(socket: UdpSocket, context: Context, mut shutdown_trigger_receiver: Receiver<()>) {
+    let mut buf = vec![0; context.max_message_size];
+    loop {
+        let received = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            received = socket.recv(&mut buf) => received,
+        };
+        match received {
+            Ok(_) if context.sink.saturated() => context.sink.rejected("backpressure"),
+            Ok(len) => {
+                if let Err(e) = context.handle(&buf[..len], None).await {
+                    log::warn!("{e}");
+                }
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+}
+
+async fn connection<S>(
+    stream: S,
+    subject: Option<ClientSubject>,
+    context: Context,
+    mut shutdown_trigger_receiver: Receiver<()>,
+) -> Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let subject = subject.map(|ClientSubject(subject)| subject);
+    let mut reader = BufReader::new(stream);
+    let mut frame = Vec::new();
+    loop {
+        let read = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            read = async {
+                backpressure(&context.sink).await;
+                read_frame(&mut reader, context.max_message_size, &mut frame).await
+            } => read?,
+        };
+        if !read {
+            break;
+        }
+        context.handle(&frame, subject.as_deref()).await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Server for SyslogServer {
+    async fn run(
+        &self,
+        sink: SharedSink,
+        shutdown_trigger_receiver: Receiver<()>,
+        _shutdown_sender: Sender<()>,
+    ) -> Result<()> {
+        let (entries, rcv) = mpsc::channel(1);
+        let context = Context {
+            sink: sink.clone(),
+            entries,
+            key_by_hostname: self.key_by_hostname,
+            max_message_size: self.max_message_size,
+        };
+        match self.transport {
+            Transport::Udp => {
+                if self.tls.tls_cert.is_some() {
+                    bail!("TLS requires --transport tcp");
+                }
+                let socket = UdpSocket::bind(&self.address).await?;
+                tokio::spawn(udp(socket, context, shutdown_trigger_receiver));
+            }
+            Transport::Tcp => {
+                let acceptor = TlsAcceptor::new(&self.tls)?;
+                let listener = TcpListener::bind(&self.address).await?;
+                tokio::spawn(serve_streams(
+                    listener,
+                    acceptor,
+                    shutdown_trigger_receiver,
+                    move |stream, subject, shutdown_trigger_receiver| {
+                        connection(stream, subject, context.clone(), shutdown_trigger_receiver)
+                    },
+                ));
+            }
+        }
+
+        let sink = &sink;
+        let subject_header = self.tls.tls_subject_header.as_deref();
+        ReceiverStream::new(rcv)
+            .for_each_concurrent(self.concurrency_limit, |entry| async move {
+                let mut headers = Vec::new();
+                if let (Some(header), Some(subject)) = (subject_header, &entry.subject) {
+                    headers.push((header, subject.as_bytes()));
+                }
+                let message = Message {
+                    topic: None,
+                    key: entry.key.as_deref().map(str::as_bytes),
+                    headers,
+                    payload: &entry.payload,
+                };
+                if let Err(e) = sink.send_message(message).await {
+                    log::warn!("{e}");
+                }
+            })
+            .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Syslog, parse};
+    use crate::cli::{SyslogServer, Tls, Transport};
+    use crate::server::tests::{RunningServer, free_tcp_address, free_udp_address, retry};
+    use serde_json::{Value, json};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpStream, UdpSocket};
+
+    fn server(address: std::net::SocketAddr, transport: Transport) -> SyslogServer {
+        SyslogServer {
+            address,
+            transport,
+            concurrency_limit: 16,
+            max_message_size: 1024,
+            key_by_hostname: true,
+            tls: Tls::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_rfc5424() {
+        let syslog = parse(
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 \
+             [exampleSDID@32473 iut=\"3\" eventSource=\"App\\]lication\"][meta seq=\"1\"] \u{feff}An application event\n",
+        );
+        assert_eq!(
+            syslog.record(),
+            json!({
+                "facility": 20,
+                "severity": 5,
+                "version": 1,
+                "timestamp": "2003-10-11T22:14:15.003Z",
+                "hostname": "mymachine.example.com",
+                "app": "evntslog",
+                "procid": null,
+                "msgid": "ID47",
+                "structured_data": {
+                    "exampleSDID@32473": { "iut": "3", "eventSource": "App]lication" },
+                    "meta": { "seq": "1" },
+                },
+                "message": "An application event",
+            })
+        );
+        let syslog = parse("<34>1 - - - - - -");
+        assert_eq!(syslog.version, Some(1));
+        assert_eq!(syslog.message, None);
+        assert!(syslog.structured_data.is_empty());
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        assert_eq!(
+            parse("<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed for lonvick"),
+            Syslog {
+                priority: 34,
+                timestamp: Some("Oct 11 22:14:15"),
+                hostname: Some("mymachine"),
+                app: Some("su"),
+                procid: Some("123"),
+                message: Some("'su root' failed for lonvick"),
+                ..Syslog::default()
+            }
+        );
+        assert_eq!(
+            parse("<13>Feb  5 17:32:18 cron: job started"),
+            Syslog {
+                priority: 13,
+                timestamp: Some("Feb  5 17:32:18"),
+                app: Some("cron"),
+                message: Some("job started"),
+                ..Syslog::default()
+            }
+        );
+        assert_eq!(
+            parse("no priority"),
+            Syslog {
+                priority: 13,
+                message: Some("no priority"),
+                ..Syslog::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let address = free_tcp_address();
+        let server = RunningServer::start(server(address, Transport::Tcp));
+
+        let mut stream = retry(|| async { Ok(TcpStream::connect(address).await?) }).await;
+        let octet_counted = "<34>1 - host app - - - counted\nmessage";
+        let frames = format!(
+            "<34>Oct 11 22:14:15 host app: first\n{} {octet_counted}",
+            octet_counted.len()
+        );
+        stream.write_all(frames.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let messages: Vec<Value> = server
+            .sink
+            .wait_for(2)
+            .await
+            .iter()
+            .map(|message| serde_json::from_slice(message).unwrap())
+            .collect();
+        assert_eq!(messages[0]["message"], "first");
+        assert_eq!(messages[1]["message"], "counted\nmessage");
+        assert_eq!(server.sink.keys().await, vec![Some(b"host".to_vec()); 2]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_udp() {
+        let address = free_udp_address();
+        let server = RunningServer::start(server(address, Transport::Udp));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let wait = async {
+            loop {
+                socket
+                    .send_to(b"<11>1 - - app - - - failed", address)
+                    .await
+                    .unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        };
+        let messages = tokio::select! {
+            _ = wait => unreachable!(),
+            messages = server.sink.wait_for(1) => messages,
+        };
+        let message: Value = serde_json::from_slice(&messages[0]).unwrap();
+        assert_eq!(message["facility"], 1);
+        assert_eq!(message["severity"], 3);
+        assert_eq!(message["app"], "app");
+        assert_eq!(server.sink.keys().await[0], None);
+
+        server.stop().await;
+    }
+}
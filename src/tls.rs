@@ -0,0 +1,286 @@
+/*
+ * Copyright 2026 Michael Krolikowski
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, bail};
+use axum::Router;
+use axum::extract::{ConnectInfo, Request};
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use rustls::RootCertStore;
+use rustls::ServerConfig;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::Receiver;
+use tokio_rustls::server::TlsStream;
+use tower::ServiceExt;
+
+use crate::cli::tls::Tls;
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> Stream for S {}
+
+#[derive(Debug, Clone)]
+pub struct ClientSubject(pub String);
+
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    tls: Tls,
+    http: bool,
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn modified(path: Option<&Path>) -> Option<SystemTime> {
+    std::fs::metadata(path?).and_then(|m| m.modified()).ok()
+}
+
+impl TlsAcceptor {
+    pub fn new(tls: &Tls) -> Result<Option<TlsAcceptor>> {
+        TlsAcceptor::build(tls, false)
+    }
+
+    // Only HTTP servers advertise h2 and http/1.1 via ALPN
+    pub fn http(tls: &Tls) -> Result<Option<TlsAcceptor>> {
+        TlsAcceptor::build(tls, true)
+    }
+
+    fn build(tls: &Tls, http: bool) -> Result<Option<TlsAcceptor>> {
+        if tls.tls_cert.is_none() {
+            return Ok(None);
+        }
+        let config = TlsAcceptor::load(tls, http)?;
+        Ok(Some(TlsAcceptor {
+            tls: tls.clone(),
+            http,
+            config: Arc::new(RwLock::new(Arc::new(config))),
+        }))
+    }
+
+    fn load(tls: &Tls, http: bool) -> Result<ServerConfig> {
+        let (Some(cert), Some(key)) = (&tls.tls_cert, &tls.tls_key) else {
+            bail!("TLS requires a certificate and a key");
+        };
+        let certs = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("Failed to read {}", cert.display()))?;
+        let key = PrivateKeyDer::from_pem_file(key)
+            .with_context(|| format!("Failed to read {}", key.display()))?;
+
+        let builder = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &tls.tls_client_ca {
+            None => builder.with_no_client_auth(),
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(ca)
+                    .with_context(|| format!("Failed to read {}", ca.display()))?
+                {
+                    roots.add(cert?)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider())
+                        .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let mut config = builder.with_single_cert(certs, key)?;
+        if http {
+            config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        }
+        Ok(config)
+    }
+
+    fn modified(&self) -> [Option<SystemTime>; 3] {
+        [
+            modified(self.tls.tls_cert.as_deref()),
+            modified(self.tls.tls_key.as_deref()),
+            modified(self.tls.tls_client_ca.as_deref()),
+        ]
+    }
+
+    pub async fn watch(self) {
+        let mut last_modified = self.modified();
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        loop {
+            interval.tick().await;
+            let modified = self.modified();
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            match TlsAcceptor::load(&self.tls, self.http) {
+                Ok(config) => {
+                    log::info!("Reloaded TLS certificates");
+                    *self.config.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+                }
+                Err(e) => log::warn!("Failed to reload TLS certificates: {e:#}"),
+            }
+        }
+    }
+
+    pub async fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> Result<(TlsStream<TcpStream>, Option<ClientSubject>)> {
+        let config = self
+            .config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let accept = tokio_rustls::TlsAcceptor::from(config).accept(stream);
+        let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, accept)
+            .await
+            .context("TLS handshake timed out")??;
+        let subject = match stream.get_ref().1.peer_certificates() {
+            Some([cert, ..]) => {
+                let (_, cert) = x509_parser::parse_x509_certificate(cert)?;
+                Some(ClientSubject(cert.subject().to_string()))
+            }
+            _ => None,
+        };
+        Ok((stream, subject))
+    }
+}
+
+pub async fn serve<F>(
+    listener: TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    app: Router,
+    shutdown: F,
+) -> Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let Some(acceptor) = acceptor else {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await?;
+        return Ok(());
+    };
+
+    let watch = tokio::spawn(acceptor.clone().watch());
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::warn!("Failed to accept connection: {e}");
+                    continue;
+                }
+            },
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let (stream, subject) = match acceptor.accept(stream).await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::debug!("TLS handshake with {peer} failed: {e}");
+                    return;
+                }
+            };
+            let service = service_fn(move |mut request: Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                if let Some(subject) = &subject {
+                    request.extensions_mut().insert(subject.clone());
+                }
+                app.clone().oneshot(request)
+            });
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(connection).await {
+                log::debug!("Connection with {peer} failed: {e}");
+            }
+        });
+    }
+    drop(listener);
+    watch.abort();
+    graceful.shutdown().await;
+    Ok(())
+}
+
+pub async fn serve_streams<F, Fut>(
+    listener: TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    mut shutdown_trigger_receiver: Receiver<()>,
+    connection: F,
+) where
+    F: Fn(Box<dyn Stream>, Option<ClientSubject>, Receiver<()>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let watch = acceptor
+        .clone()
+        .map(|acceptor| tokio::spawn(acceptor.watch()));
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = shutdown_trigger_receiver.recv() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::warn!("Failed to accept connection: {e}");
+                    continue;
+                }
+            },
+        };
+        let acceptor = acceptor.clone();
+        let connection = connection.clone();
+        let shutdown_trigger_receiver = shutdown_trigger_receiver.resubscribe();
+        tokio::spawn(async move {
+            let result = match acceptor {
+                None => connection(Box::new(stream), None, shutdown_trigger_receiver).await,
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok((stream, subject)) => {
+                        connection(Box::new(stream), subject, shutdown_trigger_receiver).await
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            if let Err(e) = result {
+                log::warn!("Connection with {peer} failed: {e}");
+            }
+        });
+    }
+    if let Some(watch) = watch {
+        watch.abort();
+    }
+}